@@ -0,0 +1,10 @@
+# Passed to sdl2-sys's bundled SDL build through SDL2_TOOLCHAIN, see
+# .cargo/config.toml. The offscreen driver is off by default upstream.
+set(VIDEO_OFFSCREEN ON CACHE BOOL "Use offscreen video driver" FORCE)
+
+# sdl2-sys only adds these flags itself when no toolchain file is given.
+if(CMAKE_HOST_WIN32)
+  set(CMAKE_C_FLAGS_INIT "-D__FLTUSED__")
+else()
+  set(CMAKE_C_FLAGS_INIT "-fcommon")
+endif()
@@ -0,0 +1,255 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, FieldsNamed, Lit, Meta, NestedMeta};
+
+// `#[vertex(normalized)]` maps integer fields to normalized floats and
+// `#[vertex(location = N)]` overrides the attribute location, which otherwise
+// follows field order.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_vertex(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// Uploads every field under its own name, or `#[uniform(name = "...")]`.
+#[proc_macro_derive(Uniforms, attributes(uniform))]
+pub fn derive_uniforms(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_uniforms(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand_vertex(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = named_fields(input, "Vertex")?;
+
+    if !has_repr_c(input) {
+        return Err(syn::Error::new_spanned(
+            name,
+            "#[derive(Vertex)] requires #[repr(C)] so field offsets are stable",
+        ));
+    }
+
+    let mut attributes = Vec::new();
+    let mut next_location: u32 = 0;
+
+    for field in fields.named.iter() {
+        let field_name = field.ident.as_ref().unwrap();
+        let field_type = &field.ty;
+        let mut normalized = false;
+        let mut location = next_location;
+
+        for meta in field_options(&field.attrs, "vertex")? {
+            match meta {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("normalized") => {
+                    normalized = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("location") => {
+                    location = match &pair.lit {
+                        Lit::Int(value) => value.base10_parse()?,
+                        lit => return Err(syn::Error::new_spanned(lit, "expected an integer")),
+                    };
+                }
+                meta => return Err(syn::Error::new_spanned(meta, "unknown vertex option")),
+            }
+        }
+
+        next_location = location + 1;
+
+        let method = if normalized {
+            quote!(normalized_attribute)
+        } else {
+            quote!(attribute)
+        };
+
+        attributes.push(quote! {
+            .#method(
+                #location,
+                <#field_type as ::rlib::gfx::vertex_array::AttributeFormat>::COMPONENTS,
+                <#field_type as ::rlib::gfx::vertex_array::AttributeFormat>::TYPE,
+                ::core::mem::offset_of!(Self, #field_name),
+            )
+        });
+    }
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rlib::gfx::vertex_array::Vertex for #name #type_generics #where_clause {
+            fn layout() -> ::rlib::gfx::vertex_array::VertexLayout {
+                ::rlib::gfx::vertex_array::VertexLayout::new(::std::mem::size_of::<#name>())
+                    #(#attributes)*
+            }
+        }
+    })
+}
+
+fn expand_uniforms(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = named_fields(input, "Uniforms")?;
+    let mut uploads = Vec::new();
+
+    for field in fields.named.iter() {
+        let field_name = field.ident.as_ref().unwrap();
+        let mut uniform_name = field_name.to_string();
+
+        for meta in field_options(&field.attrs, "uniform")? {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("name") => {
+                    uniform_name = match &pair.lit {
+                        Lit::Str(value) => value.value(),
+                        lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                    };
+                }
+                meta => return Err(syn::Error::new_spanned(meta, "unknown uniform option")),
+            }
+        }
+
+        uploads.push(quote! {
+            program.set_uniform(#uniform_name, &self.#field_name);
+        });
+    }
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rlib::gfx::uniform::Uniforms for #name #type_generics #where_clause {
+            fn apply(&self, program: &::rlib::gfx::shader::ShaderProgram) {
+                #(#uploads)*
+            }
+        }
+    })
+}
+
+fn named_fields<'a>(input: &'a DeriveInput, derive: &str) -> syn::Result<&'a FieldsNamed> {
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => Ok(fields),
+            _ => Err(syn::Error::new_spanned(
+                &input.ident,
+                format!("#[derive({})] requires a struct with named fields", derive),
+            )),
+        },
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            format!("#[derive({})] only supports structs", derive),
+        )),
+    }
+}
+
+fn field_options(attrs: &[syn::Attribute], name: &str) -> syn::Result<Vec<NestedMeta>> {
+    let mut options = Vec::new();
+
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident(name)) {
+        match attr.parse_meta()? {
+            Meta::List(list) => options.extend(list.nested),
+            meta => {
+                return Err(syn::Error::new_spanned(
+                    meta,
+                    format!("expected #[{}(...)]", name),
+                ))
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+fn has_repr_c(input: &DeriveInput) -> bool {
+    input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("repr"))
+        .filter_map(|attr| attr.parse_meta().ok())
+        .any(|meta| match meta {
+            Meta::List(list) => list.nested.iter().any(
+                |nested| matches!(nested, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("C")),
+            ),
+            _ => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn vertex_offsets_need_no_unsafe() {
+        let input: DeriveInput = parse_quote! {
+            #[repr(C)]
+            struct Point {
+                position: [f32; 2],
+                #[vertex(normalized, location = 3)]
+                color: [u8; 4],
+            }
+        };
+
+        let expanded = expand_vertex(&input).unwrap().to_string();
+        assert!(!expanded.contains("unsafe"), "{}", expanded);
+        assert!(expanded.contains(&quote!(::core::mem::offset_of!(Self, color)).to_string()));
+        assert!(
+            expanded.contains("normalized_attribute (3u32"),
+            "{}",
+            expanded
+        );
+    }
+
+    #[test]
+    fn vertex_requires_repr_c() {
+        let input: DeriveInput = parse_quote! {
+            struct Point {
+                position: [f32; 2],
+            }
+        };
+
+        assert!(expand_vertex(&input).is_err());
+    }
+
+    #[test]
+    fn uniforms_upload_each_field_under_its_name() {
+        let input: DeriveInput = parse_quote! {
+            struct Material {
+                tint: Color,
+                #[uniform(name = "u_strength")]
+                strength: f32,
+            }
+        };
+
+        let expected = quote! {
+            impl ::rlib::gfx::uniform::Uniforms for Material {
+                fn apply(&self, program: &::rlib::gfx::shader::ShaderProgram) {
+                    program.set_uniform("tint", &self.tint);
+                    program.set_uniform("u_strength", &self.strength);
+                }
+            }
+        };
+
+        assert_eq!(
+            expand_uniforms(&input).unwrap().to_string(),
+            expected.to_string()
+        );
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        let input: DeriveInput = parse_quote! {
+            struct Material {
+                #[uniform(nmae = "typo")]
+                strength: f32,
+            }
+        };
+
+        assert!(expand_uniforms(&input).is_err());
+    }
+}
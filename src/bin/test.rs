@@ -3,8 +3,6 @@ use rlib::*;
 
 struct Application {
     texture: gfx::texture::Texture,
-    wall_texture: gfx::texture::Texture,
-    batch: gfx::sprite_batch::SpriteBatch<1000>,
     test_val: f32,
     sprites: Vec<gfx::sprite_batch::Sprite>,
     lmao: f32,
@@ -12,19 +10,13 @@ struct Application {
 }
 
 impl rlib::RLibApp for Application {
-    fn new() -> Self {
+    fn new(_ctx: &mut Context) -> Self {
         let texture = gfx::texture::Texture::from_file("data/amogus.png").unwrap();
-        let wall_texture = gfx::texture::Texture::from_file("data/stonewall.png").unwrap();
 
-        let sprites = vec![
-            gfx::sprite_batch::Sprite::new(texture.clone()),
-            // gfx::sprite_batch::Sprite::new(wall_texture.clone()),
-        ];
+        let sprites = vec![gfx::sprite_batch::Sprite::new(texture.clone())];
 
         Self {
             texture,
-            wall_texture,
-            batch: gfx::sprite_batch::SpriteBatch::new().unwrap(),
             test_val: 0.0,
             sprites,
             lmao: 0.0,
@@ -32,11 +24,8 @@ impl rlib::RLibApp for Application {
         }
     }
 
-    fn init(&mut self) {
-        unsafe {
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-        }
+    fn init(&mut self, ctx: &mut Context) {
+        ctx.get_gfx().set_alpha_blending(true);
 
         {
             let sprite = &mut self.sprites[0];
@@ -49,36 +38,29 @@ impl rlib::RLibApp for Application {
         }
     }
 
-    fn on_key(&mut self, scancode: u32) {
-        if scancode == 26 {
-            self.sprites[0].rotation += 15.0;
-        }
+    fn update(&mut self, ctx: &mut Context, dt: f32) {
+        self.test_val += 0.04 * dt;
 
-        if scancode == 22 {
-            self.lmao2 -= 0.006;
-        }
+        let actions = ctx.get_input().actions();
 
-        if scancode == 7 {
-            self.lmao += 0.006;
+        if actions.pressed("rotate") {
+            self.sprites[0].rotation += 15.0;
         }
 
-        if scancode == 4 {
-            self.lmao -= 0.006;
-        }
+        self.lmao += actions.axis("move_x") * 0.5 * dt;
+        self.lmao2 += actions.axis("move_y") * 0.5 * dt;
     }
 
-    fn render(&mut self) {
-        unsafe {
-            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
-        }
+    fn render(&mut self, ctx: &mut Context) {
+        ctx.get_gfx()
+            .clear(color::Color::from_rgba(0.0, 0.0, 0.0, 1.0));
 
         let x = f32::sin(self.test_val);
-        self.test_val += 0.0004;
 
-        self.batch.begin_batch();
+        let batch = ctx.get_gfx_mut().get_sprite_batch();
+        batch.begin_batch();
         {
-            self.batch.draw(
+            batch.draw(
                 &self.texture,
                 -0.25 - x,
                 0.0,
@@ -86,21 +68,30 @@ impl rlib::RLibApp for Application {
                 0.5,
                 Some(color::Color::from_rgba(1.0, x, 0.0, 1.0)),
             );
-            self.batch
-                .draw(&self.texture, self.lmao, self.lmao2, 0.1, 0.1, None);
+            batch.draw(&self.texture, self.lmao, self.lmao2, 0.1, 0.1, None);
 
             for spr in self.sprites.iter() {
-                self.batch.draw_sprite(spr);
+                batch.draw_sprite(spr);
             }
         }
-        self.batch.end_batch();
+        batch.end_batch();
     }
 }
 
 fn main() {
-    rlib::init::<Application>(rlib::RlibConfig {
+    let result = rlib::init::<Application>(rlib::RlibConfig {
         window_title: "Rlib Test".to_string(),
         window_width: 1600,
         window_height: 900,
+        action_bindings: Some("data/bindings.toml".to_string()),
+        ..Default::default()
     });
+
+    match result {
+        Ok(status) => std::process::exit(status),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,170 @@
+use crate::error::RlibError;
+use crate::gfx::debug::DebugSeverity;
+use crate::headless::HeadlessConfig;
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// The renderer uses direct state access and program interface queries
+// unconditionally, so nothing older will do.
+pub const MIN_GL_VERSION: (u8, u8) = (4, 5);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    Windowed,
+    Fullscreen,
+    Borderless,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VSync {
+    Off,
+    On,
+    Adaptive,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RlibConfig {
+    pub window_title: String,
+    pub window_width: u32,
+    pub window_height: u32,
+    pub min_window_size: Option<(u32, u32)>,
+    pub window_mode: WindowMode,
+    pub resizable: bool,
+    pub high_dpi: bool,
+    pub vsync: VSync,
+    pub max_fps: Option<u32>,
+    pub pause_when_minimized: bool,
+    pub msaa_samples: u8,
+    pub depth_bits: u8,
+    pub stencil_bits: u8,
+    pub gl_version: (u8, u8),
+    pub gl_fallback_versions: Vec<(u8, u8)>,
+    pub gl_debug: bool,
+    pub gl_debug_min_severity: DebugSeverity,
+    pub gl_debug_ignored_ids: Vec<u32>,
+    // Where `ShaderCache` looks up shader files and their includes.
+    pub shader_root: String,
+    // Reloads watched textures and shader programs when their files change.
+    pub hot_reload: bool,
+    // Directory for linked program binaries, which saves compiling and
+    // linking shaders again on later launches. Off when `None`.
+    pub program_cache_dir: Option<String>,
+    pub log_level: LevelFilter,
+    pub log_file: Option<String>,
+    pub tick_rate: u32,
+    pub max_frame_time: f32,
+    pub controller_mappings: Option<String>,
+    pub controller_dead_zone: f32,
+    pub action_bindings: Option<String>,
+    pub record_input: Option<String>,
+    pub replay_input: Option<String>,
+    pub headless: Option<HeadlessConfig>,
+}
+
+impl Default for RlibConfig {
+    fn default() -> RlibConfig {
+        RlibConfig {
+            window_title: "Title".to_string(),
+            window_width: 800,
+            window_height: 600,
+            min_window_size: None,
+            window_mode: WindowMode::Windowed,
+            resizable: true,
+            high_dpi: false,
+            vsync: VSync::On,
+            max_fps: None,
+            pause_when_minimized: true,
+            msaa_samples: 0,
+            depth_bits: 24,
+            stencil_bits: 8,
+            gl_version: (4, 5),
+            gl_fallback_versions: Vec::new(),
+            gl_debug: false,
+            gl_debug_min_severity: DebugSeverity::Low,
+            gl_debug_ignored_ids: Vec::new(),
+            shader_root: "data/shaders".to_string(),
+            hot_reload: false,
+            program_cache_dir: None,
+            log_level: LevelFilter::Trace,
+            log_file: None,
+            tick_rate: 60,
+            max_frame_time: 0.25,
+            controller_mappings: None,
+            controller_dead_zone: 0.15,
+            action_bindings: None,
+            record_input: None,
+            replay_input: None,
+            headless: None,
+        }
+    }
+}
+
+impl RlibConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<RlibConfig, RlibError> {
+        let source = std::fs::read_to_string(path)?;
+        RlibConfig::from_toml(&source)
+    }
+
+    pub fn from_toml(source: &str) -> Result<RlibConfig, RlibError> {
+        let config: RlibConfig =
+            toml::from_str(source).map_err(|e| RlibError::Parse(e.to_string()))?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), RlibError> {
+        for &version in std::iter::once(&self.gl_version).chain(&self.gl_fallback_versions) {
+            if version < MIN_GL_VERSION {
+                return Err(RlibError::Parse(format!(
+                    "OpenGL {}.{} is older than the {}.{} rlib requires",
+                    version.0, version.1, MIN_GL_VERSION.0, MIN_GL_VERSION.1
+                )));
+            }
+        }
+
+        // `FixedTimestep` turns this into a `Duration`, which panics on
+        // negative, NaN or out of range values.
+        if self.max_frame_time <= 0.0
+            || std::time::Duration::try_from_secs_f32(self.max_frame_time).is_err()
+        {
+            return Err(RlibError::Parse(format!(
+                "max_frame_time must be a positive number of seconds, got {}",
+                self.max_frame_time
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> Result<String, RlibError> {
+        toml::to_string_pretty(self).map_err(|e| RlibError::Parse(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_gl_versions_are_rejected() {
+        assert!(RlibConfig::from_toml("gl_fallback_versions = [[4, 6], [4, 5]]").is_ok());
+        assert!(RlibConfig::from_toml("gl_fallback_versions = [[4, 5], [3, 3]]").is_err());
+        assert!(RlibConfig::from_toml("gl_version = [4, 1]").is_err());
+        assert!(RlibConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn bad_max_frame_times_are_rejected() {
+        assert!(RlibConfig::from_toml("max_frame_time = 0.1").is_ok());
+        assert!(RlibConfig::from_toml("max_frame_time = 0.0").is_err());
+        assert!(RlibConfig::from_toml("max_frame_time = -0.25").is_err());
+        assert!(RlibConfig::from_toml("max_frame_time = nan").is_err());
+        assert!(RlibConfig::from_toml("max_frame_time = inf").is_err());
+        assert!(RlibConfig::from_toml("max_frame_time = 1e30").is_err());
+    }
+}
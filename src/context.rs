@@ -0,0 +1,52 @@
+use crate::gfx::graphics::Graphics;
+use crate::input::Input;
+use crate::time::Time;
+use crate::window::Window;
+
+// Fields drop in declaration order; the graphics context has to go before the
+// window it was created for.
+pub struct Context {
+    pub(crate) gfx: Graphics,
+    pub(crate) window: Window,
+    pub(crate) input: Input,
+    pub(crate) time: Time,
+    pub(crate) quit_requested: bool,
+}
+
+impl Context {
+    pub fn get_window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn get_window_mut(&mut self) -> &mut Window {
+        &mut self.window
+    }
+
+    pub fn get_input(&self) -> &Input {
+        &self.input
+    }
+
+    pub fn get_input_mut(&mut self) -> &mut Input {
+        &mut self.input
+    }
+
+    pub fn get_time(&self) -> &Time {
+        &self.time
+    }
+
+    pub fn get_gfx(&self) -> &Graphics {
+        &self.gfx
+    }
+
+    pub fn get_gfx_mut(&mut self) -> &mut Graphics {
+        &mut self.gfx
+    }
+
+    pub fn quit(&mut self) {
+        self.quit_requested = true;
+    }
+
+    pub fn is_quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+}
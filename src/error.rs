@@ -0,0 +1,93 @@
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum RlibError {
+    // `path` is the file involved, where it's known.
+    Io {
+        path: Option<PathBuf>,
+        source: std::io::Error,
+    },
+    ImageDecode(image::ImageError),
+    ShaderCompile {
+        stage: u32,
+        log: String,
+    },
+    ProgramLink {
+        log: String,
+    },
+    GlResource(String),
+    GlVersion {
+        required: (u8, u8),
+        actual: (i32, i32),
+    },
+    Sdl(String),
+    Parse(String),
+}
+
+pub(crate) fn shader_stage_name(stage: u32) -> &'static str {
+    match stage {
+        gl::VERTEX_SHADER => "vertex",
+        gl::FRAGMENT_SHADER => "fragment",
+        gl::GEOMETRY_SHADER => "geometry",
+        gl::TESS_CONTROL_SHADER => "tessellation control",
+        gl::TESS_EVALUATION_SHADER => "tessellation evaluation",
+        gl::COMPUTE_SHADER => "compute",
+        _ => "unknown",
+    }
+}
+
+impl fmt::Display for RlibError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RlibError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "I/O error: {}: {}", path.display(), source),
+            RlibError::Io { path: None, source } => write!(f, "I/O error: {}", source),
+            RlibError::ImageDecode(e) => write!(f, "Failed to decode image: {}", e),
+            RlibError::ShaderCompile { stage, log } => write!(
+                f,
+                "Failed to compile {} shader: {}",
+                shader_stage_name(*stage),
+                log.trim_end()
+            ),
+            RlibError::ProgramLink { log } => {
+                write!(f, "Failed to link shader program: {}", log.trim_end())
+            }
+            RlibError::GlResource(what) => write!(f, "Failed to create GL resource: {}", what),
+            RlibError::GlVersion { required, actual } => write!(
+                f,
+                "OpenGL {}.{} or newer is required, got {}.{}",
+                required.0, required.1, actual.0, actual.1
+            ),
+            RlibError::Sdl(e) => write!(f, "SDL error: {}", e),
+            RlibError::Parse(e) => write!(f, "Parse error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RlibError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RlibError::Io { source, .. } => Some(source),
+            RlibError::ImageDecode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RlibError {
+    fn from(e: std::io::Error) -> RlibError {
+        RlibError::Io {
+            path: None,
+            source: e,
+        }
+    }
+}
+
+impl From<image::ImageError> for RlibError {
+    fn from(e: image::ImageError) -> RlibError {
+        RlibError::ImageDecode(e)
+    }
+}
@@ -1,154 +1,333 @@
+use super::debug;
+use super::deletion_queue::{self, GlObject};
+use super::resource_tracker::{self, ResourceKind};
+use super::state_cache;
+use crate::error::RlibError;
+use bytemuck::Pod;
 use std::cell::Cell;
+use std::marker::PhantomData;
+use std::ops::Range;
 use std::rc::Rc;
 
 extern crate log;
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BufferTarget {
+    Vertex,
+    Index,
+    Uniform,
+    ShaderStorage,
+    DrawIndirect,
+    CopyRead,
+    CopyWrite,
+}
+
+impl BufferTarget {
+    pub fn to_gl(self) -> u32 {
+        match self {
+            BufferTarget::Vertex => gl::ARRAY_BUFFER,
+            BufferTarget::Index => gl::ELEMENT_ARRAY_BUFFER,
+            BufferTarget::Uniform => gl::UNIFORM_BUFFER,
+            BufferTarget::ShaderStorage => gl::SHADER_STORAGE_BUFFER,
+            BufferTarget::DrawIndirect => gl::DRAW_INDIRECT_BUFFER,
+            BufferTarget::CopyRead => gl::COPY_READ_BUFFER,
+            BufferTarget::CopyWrite => gl::COPY_WRITE_BUFFER,
+        }
+    }
+}
+
+// Only a hint for now; every buffer other than a `StreamBuffer`'s is created
+// with `DYNAMIC_STORAGE_BIT` so it can be written to regardless.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BufferUsage {
+    Static,
+    Dynamic,
+    Stream,
+}
+
+pub trait IndexType: Pod {
+    const GL_TYPE: u32;
+}
+
+impl IndexType for u16 {
+    const GL_TYPE: u32 = gl::UNSIGNED_SHORT;
+}
+
+impl IndexType for u32 {
+    const GL_TYPE: u32 = gl::UNSIGNED_INT;
+}
+
 pub(crate) struct NativeBuffer {
-    handle: u32,
+    // Storage is immutable, so growing a buffer swaps in a new GL object.
+    handle: Cell<u32>,
     size: Cell<isize>,
-    buffer_type: u32,
-    usage: u32,
+    target: BufferTarget,
+    usage: BufferUsage,
+}
+
+impl NativeBuffer {
+    pub(crate) fn get_handle(&self) -> u32 {
+        self.handle.get()
+    }
+}
+
+impl Drop for NativeBuffer {
+    fn drop(&mut self) {
+        resource_tracker::unregister(ResourceKind::Buffer, self.handle.get());
+        deletion_queue::queue_delete(GlObject::Buffer(self.handle.get()));
+    }
 }
 
 #[derive(Clone)]
-pub struct Buffer {
+pub struct Buffer<T: Pod> {
     pub(crate) handle: Rc<NativeBuffer>,
+    _marker: PhantomData<T>,
 }
 
-impl Buffer {
-    pub fn new(buffer_type: u32, usage: u32) -> Option<Buffer> {
-        let mut handle: u32 = 0;
+impl<T: Pod> Buffer<T> {
+    #[track_caller]
+    pub fn new(target: BufferTarget, usage: BufferUsage) -> Result<Buffer<T>, RlibError> {
+        Buffer::with_capacity(target, usage, 0)
+    }
 
-        unsafe {
-            gl::GenBuffers(1, &mut handle);
-        }
+    #[track_caller]
+    pub fn with_capacity(
+        target: BufferTarget,
+        usage: BufferUsage,
+        len: usize,
+    ) -> Result<Buffer<T>, RlibError> {
+        Buffer::with_storage_flags(target, usage, len, gl::DYNAMIC_STORAGE_BIT)
+    }
 
-        if handle == 0 {
-            None
-        } else {
-            Some(Buffer {
-                handle: Rc::new(NativeBuffer {
-                    handle,
-                    size: Cell::new(0),
-                    buffer_type,
-                    usage,
-                }),
-            })
-        }
+    // Buffers created without `DYNAMIC_STORAGE_BIT` can't be written through
+    // `set`/`update`, only through a mapping.
+    #[track_caller]
+    pub(crate) fn with_storage_flags(
+        target: BufferTarget,
+        usage: BufferUsage,
+        len: usize,
+        flags: u32,
+    ) -> Result<Buffer<T>, RlibError> {
+        let byte_count = (len * std::mem::size_of::<T>()) as isize;
+        let handle = create_buffer(byte_count, std::ptr::null(), flags)?;
+
+        resource_tracker::register(
+            ResourceKind::Buffer,
+            handle,
+            None,
+            0,
+            byte_count as usize,
+            std::panic::Location::caller(),
+        );
+
+        Ok(Buffer {
+            handle: Rc::new(NativeBuffer {
+                handle: Cell::new(handle),
+                size: Cell::new(byte_count),
+                target,
+                usage,
+            }),
+            _marker: PhantomData,
+        })
     }
 
-    pub fn new_with_capacity(buffer_type: u32, usage: u32, capacity: u32) -> Option<Buffer> {
-        let mut handle: u32 = 0;
+    #[track_caller]
+    pub fn from_slice(
+        target: BufferTarget,
+        usage: BufferUsage,
+        data: &[T],
+    ) -> Result<Buffer<T>, RlibError> {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let handle = create_buffer(
+            bytes.len() as isize,
+            bytes.as_ptr(),
+            gl::DYNAMIC_STORAGE_BIT,
+        )?;
 
-        unsafe {
-            gl::GenBuffers(1, &mut handle);
-            gl::BindBuffer(buffer_type, handle);
-            gl::BufferData(buffer_type, capacity as isize, std::ptr::null(), usage);
-        }
+        resource_tracker::register(
+            ResourceKind::Buffer,
+            handle,
+            None,
+            0,
+            bytes.len(),
+            std::panic::Location::caller(),
+        );
 
-        if handle == 0 {
-            None
-        } else {
-            Some(Buffer {
-                handle: Rc::new(NativeBuffer {
-                    handle,
-                    size: Cell::new(capacity as isize),
-                    buffer_type,
-                    usage,
-                }),
-            })
-        }
+        Ok(Buffer {
+            handle: Rc::new(NativeBuffer {
+                handle: Cell::new(handle),
+                size: Cell::new(bytes.len() as isize),
+                target,
+                usage,
+            }),
+            _marker: PhantomData,
+        })
     }
 
     pub fn bind(&self) {
-        unsafe {
-            gl::BindBuffer(self.handle.buffer_type, self.handle.handle);
-        }
+        state_cache::bind_buffer(self.handle.target.to_gl(), self.handle.handle.get());
     }
 
     pub fn unbind(&self) {
-        unsafe {
-            gl::BindBuffer(self.handle.buffer_type, 0);
-        }
+        state_cache::bind_buffer(self.handle.target.to_gl(), 0);
     }
 
-    pub fn set_data(&mut self, data: &[f32]) {
-        let byte_count = (data.len() * std::mem::size_of::<f32>()) as isize;
-        let ptr = data.as_ptr() as *const std::os::raw::c_void;
-
-        self.handle.size.set(byte_count);
+    // Only meaningful for `Uniform` and `ShaderStorage` buffers, which are
+    // read by blocks through numbered binding points.
+    pub fn bind_base(&self, index: u32) {
+        state_cache::bind_buffer_base(self.handle.target.to_gl(), index, self.handle.handle.get());
+    }
 
-        self.bind();
-        unsafe {
-            gl::BufferData(
-                self.handle.buffer_type,
-                byte_count,
-                std::ptr::null(),
-                self.handle.usage,
-            );
-            gl::BufferData(self.handle.buffer_type, byte_count, ptr, self.handle.usage);
+    // Replaces the contents, growing the buffer if `data` doesn't fit.
+    pub fn set(&mut self, data: &[T]) {
+        if data.len() > self.get_len() {
+            self.reallocate(data.len(), false);
         }
+
+        self.update(0..data.len(), data);
     }
 
-    pub fn set_data_u32(&mut self, data: &[u32]) {
-        let byte_count = (data.len() * std::mem::size_of::<u32>()) as isize;
-        let ptr = data.as_ptr() as *const std::os::raw::c_void;
+    pub fn update(&self, range: Range<usize>, data: &[T]) {
+        assert_eq!(
+            range.len(),
+            data.len(),
+            "Buffer::update() range and data lengths differ"
+        );
+        assert!(
+            range.end <= self.get_len(),
+            "Buffer::update() range {:?} is out of bounds for length {}",
+            range,
+            self.get_len()
+        );
 
-        self.handle.size.set(byte_count);
+        if data.is_empty() {
+            return;
+        }
+
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let offset = (range.start * std::mem::size_of::<T>()) as isize;
 
-        self.bind();
         unsafe {
-            gl::BufferData(
-                self.handle.buffer_type,
-                byte_count,
-                std::ptr::null(),
-                self.handle.usage,
+            gl::NamedBufferSubData(
+                self.handle.handle.get(),
+                offset,
+                bytes.len() as isize,
+                bytes.as_ptr() as *const std::os::raw::c_void,
             );
-            gl::BufferData(self.handle.buffer_type, byte_count, ptr, self.handle.usage);
         }
     }
 
-    pub fn copy_data(&self, data: &[f32], offset: isize) {
-        let mut byte_count = (data.len() * std::mem::size_of::<f32>()) as isize;
-        let ptr = data.as_ptr() as *const std::os::raw::c_void;
+    pub fn read_back(&self) -> Vec<T> {
+        let mut data = vec![T::zeroed(); self.get_len()];
 
-        if (offset + byte_count) > self.handle.size.get() {
-            byte_count = self.handle.size.get() - offset;
+        if !data.is_empty() {
+            let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut data);
+
+            unsafe {
+                gl::GetNamedBufferSubData(
+                    self.handle.handle.get(),
+                    0,
+                    bytes.len() as isize,
+                    bytes.as_mut_ptr() as *mut std::os::raw::c_void,
+                );
+            }
         }
 
-        self.bind();
-        unsafe {
-            gl::BufferSubData(self.handle.buffer_type, offset, byte_count, ptr);
+        data
+    }
+
+    // Keeps as much of the old contents as fits in the new length.
+    pub fn resize(&mut self, len: usize) {
+        if len != self.get_len() {
+            self.reallocate(len, true);
         }
     }
 
-    pub fn copy_data_part(&self, data: &[f32], src_size: isize, dst_offset: isize) {
-        let mut byte_count = (src_size as usize * std::mem::size_of::<f32>()) as isize;
-        let ptr = data.as_ptr() as *const std::os::raw::c_void;
+    fn reallocate(&mut self, len: usize, keep_contents: bool) {
+        let byte_count = (len * std::mem::size_of::<T>()) as isize;
+        let handle = match create_buffer(byte_count, std::ptr::null(), gl::DYNAMIC_STORAGE_BIT) {
+            Ok(handle) => handle,
+            Err(e) => {
+                error!("Failed to resize buffer to {} bytes: {}", byte_count, e);
+                return;
+            }
+        };
 
-        if (dst_offset + byte_count) > self.handle.size.get() {
-            byte_count = self.handle.size.get() - dst_offset;
+        let old_handle = self.handle.handle.replace(handle);
+        let copy_size = byte_count.min(self.handle.size.get());
+        self.handle.size.set(byte_count);
+
+        if keep_contents && copy_size > 0 {
+            unsafe {
+                gl::CopyNamedBufferSubData(old_handle, handle, 0, 0, copy_size);
+            }
         }
 
-        self.bind();
-        unsafe {
-            gl::BufferSubData(self.handle.buffer_type, dst_offset, byte_count, ptr);
+        let label = resource_tracker::rehandle(ResourceKind::Buffer, old_handle, handle);
+        resource_tracker::update(ResourceKind::Buffer, handle, |info| {
+            info.byte_size = byte_count as usize;
+        });
+
+        if let Some(label) = label {
+            debug::label_object(gl::BUFFER, handle, &label);
         }
+
+        deletion_queue::queue_delete(GlObject::Buffer(old_handle));
+    }
+
+    pub fn set_label(&self, label: &str) {
+        debug::label_object(gl::BUFFER, self.handle.handle.get(), label);
+        resource_tracker::set_label(ResourceKind::Buffer, self.handle.handle.get(), label);
+    }
+
+    pub fn get_len(&self) -> usize {
+        self.handle.size.get() as usize / std::mem::size_of::<T>().max(1)
     }
 
     pub fn get_size(&self) -> isize {
         self.handle.size.get()
     }
 
-    pub fn get_type(&self) -> u32 {
-        self.handle.buffer_type
+    pub fn get_target(&self) -> BufferTarget {
+        self.handle.target
     }
 
-    pub fn get_usage(&self) -> u32 {
+    pub fn get_usage(&self) -> BufferUsage {
         self.handle.usage
     }
 
     pub fn get_handle(&self) -> u32 {
-        self.handle.handle
+        self.handle.handle.get()
+    }
+}
+
+impl<I: IndexType> Buffer<I> {
+    pub fn get_index_type(&self) -> u32 {
+        I::GL_TYPE
+    }
+}
+
+fn create_buffer(byte_count: isize, data: *const u8, flags: u32) -> Result<u32, RlibError> {
+    let mut handle: u32 = 0;
+
+    unsafe {
+        gl::CreateBuffers(1, &mut handle);
     }
+
+    if handle == 0 {
+        return Err(RlibError::GlResource("buffer".to_string()));
+    }
+
+    if byte_count > 0 {
+        unsafe {
+            gl::NamedBufferStorage(
+                handle,
+                byte_count,
+                data as *const std::os::raw::c_void,
+                flags,
+            );
+        }
+    }
+
+    Ok(handle)
 }
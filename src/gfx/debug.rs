@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::os::raw::c_void;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl DebugSeverity {
+    fn from_gl(severity: u32) -> DebugSeverity {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+            _ => DebugSeverity::Notification,
+        }
+    }
+}
+
+struct DebugFilter {
+    min_severity: DebugSeverity,
+    ignored_ids: Vec<u32>,
+}
+
+// Owns the filter the driver holds a pointer to, so the callback has to be
+// removed before this goes away.
+pub(crate) struct DebugOutput {
+    _filter: Box<DebugFilter>,
+}
+
+impl DebugOutput {
+    pub(crate) fn install(min_severity: DebugSeverity, ignored_ids: &[u32]) -> Option<DebugOutput> {
+        if !gl::DebugMessageCallback::is_loaded() {
+            warn!("GL debug output requested but glDebugMessageCallback is unavailable");
+            return None;
+        }
+
+        let filter = Box::new(DebugFilter {
+            min_severity,
+            ignored_ids: ignored_ids.to_vec(),
+        });
+
+        unsafe {
+            gl::Enable(gl::DEBUG_OUTPUT);
+            // Synchronous output keeps the callback on the GL thread and lets
+            // a breakpoint in it land on the offending call.
+            gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+            gl::DebugMessageCallback(
+                Some(debug_callback),
+                &*filter as *const DebugFilter as *const c_void,
+            );
+        }
+
+        info!("GL debug output enabled");
+
+        Some(DebugOutput { _filter: filter })
+    }
+}
+
+impl Drop for DebugOutput {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DebugMessageCallback(None, std::ptr::null());
+            gl::Disable(gl::DEBUG_OUTPUT);
+        }
+    }
+}
+
+extern "system" fn debug_callback(
+    source: u32,
+    message_type: u32,
+    id: u32,
+    severity: u32,
+    _length: i32,
+    message: *const gl::types::GLchar,
+    user_param: *mut c_void,
+) {
+    let filter = unsafe { &*(user_param as *const DebugFilter) };
+    let severity = DebugSeverity::from_gl(severity);
+
+    if severity < filter.min_severity || filter.ignored_ids.contains(&id) {
+        return;
+    }
+
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    let source = source_name(source);
+    let message_type = type_name(message_type);
+
+    match severity {
+        DebugSeverity::High => error!("GL {} {} {}: {}", source, message_type, id, message),
+        DebugSeverity::Medium => warn!("GL {} {} {}: {}", source, message_type, id, message),
+        DebugSeverity::Low => info!("GL {} {} {}: {}", source, message_type, id, message),
+        DebugSeverity::Notification => {
+            debug!("GL {} {} {}: {}", source, message_type, id, message)
+        }
+    }
+}
+
+fn source_name(source: u32) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(message_type: u32) -> &'static str {
+    match message_type {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
+
+// `identifier` is the GL namespace of `handle`, e.g. `gl::TEXTURE` or
+// `gl::BUFFER`. Contexts older than 4.3 silently skip labelling.
+pub(crate) fn label_object(identifier: u32, handle: u32, label: &str) {
+    if !gl::ObjectLabel::is_loaded() {
+        return;
+    }
+
+    unsafe {
+        gl::ObjectLabel(
+            identifier,
+            handle,
+            label.len() as i32,
+            label.as_ptr() as *const gl::types::GLchar,
+        );
+    }
+}
+
+pub fn push_debug_group(name: &str) {
+    if !gl::PushDebugGroup::is_loaded() {
+        return;
+    }
+
+    unsafe {
+        gl::PushDebugGroup(
+            gl::DEBUG_SOURCE_APPLICATION,
+            0,
+            name.len() as i32,
+            name.as_ptr() as *const gl::types::GLchar,
+        );
+    }
+}
+
+pub fn pop_debug_group() {
+    if !gl::PopDebugGroup::is_loaded() {
+        return;
+    }
+
+    unsafe {
+        gl::PopDebugGroup();
+    }
+}
+
+pub struct DebugGroup {
+    _private: (),
+}
+
+impl DebugGroup {
+    pub fn new(name: &str) -> DebugGroup {
+        push_debug_group(name);
+        DebugGroup { _private: () }
+    }
+}
+
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        pop_debug_group();
+    }
+}
@@ -0,0 +1,60 @@
+use super::state_cache;
+use std::cell::{Cell, RefCell};
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum GlObject {
+    Texture(u32),
+    Buffer(u32),
+    Shader(u32),
+    Program(u32),
+    VertexArray(u32),
+    // A `GLsync`, which is a pointer rather than a name.
+    Fence(usize),
+}
+
+// Handles can be dropped anywhere, including in the middle of a batch or after
+// the context is gone, so deletion is deferred until the loop flushes the queue
+// on the GL thread between frames.
+thread_local! {
+    static QUEUE: RefCell<Vec<GlObject>> = const { RefCell::new(Vec::new()) };
+    static CONTEXT_ALIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) fn queue_delete(object: GlObject) {
+    if CONTEXT_ALIVE.with(|alive| alive.get()) {
+        QUEUE.with(|queue| queue.borrow_mut().push(object));
+    }
+}
+
+pub(crate) fn flush() -> usize {
+    let objects = QUEUE.with(|queue| std::mem::take(&mut *queue.borrow_mut()));
+
+    for object in objects.iter() {
+        state_cache::forget(*object);
+
+        unsafe {
+            match *object {
+                GlObject::Texture(handle) => gl::DeleteTextures(1, &handle),
+                GlObject::Buffer(handle) => gl::DeleteBuffers(1, &handle),
+                GlObject::Shader(handle) => gl::DeleteShader(handle),
+                GlObject::Program(handle) => gl::DeleteProgram(handle),
+                GlObject::VertexArray(handle) => gl::DeleteVertexArrays(1, &handle),
+                GlObject::Fence(fence) => gl::DeleteSync(fence as gl::types::GLsync),
+            }
+        }
+    }
+
+    objects.len()
+}
+
+pub(crate) fn set_context_alive(alive: bool) {
+    CONTEXT_ALIVE.with(|context_alive| context_alive.set(alive));
+
+    // Whatever is still queued died with the context, and a new context
+    // starts with none of the cached state.
+    if !alive {
+        QUEUE.with(|queue| queue.borrow_mut().clear());
+    }
+
+    state_cache::invalidate();
+}
@@ -0,0 +1,244 @@
+use super::color::Color;
+use super::debug::DebugOutput;
+use super::deletion_queue;
+use super::hot_reload::HotReload;
+use super::program_cache::{self, ProgramCacheStats};
+use super::resource_tracker;
+use super::shader::ShaderProgram;
+use super::shader_cache::ShaderCache;
+use super::sprite_batch::SpriteBatch;
+use super::state_cache::{self, StateCacheStats};
+use super::stream_buffer::{self, StreamBufferStats};
+use super::texture::Texture;
+use super::uniform_buffer::{CameraBlock, UniformBuffer, CAMERA_BLOCK_BINDING};
+use crate::config::RlibConfig;
+use crate::error::RlibError;
+use glam::{Mat4, Vec2};
+use sdl2::video::GLContext;
+use std::path::Path;
+
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+// Runs after every other field of `Graphics` has dropped, so the GL objects
+// they queued are still deleted while the context exists.
+struct OwnedContext {
+    _context: GLContext,
+}
+
+impl Drop for OwnedContext {
+    fn drop(&mut self) {
+        deletion_queue::flush();
+        deletion_queue::set_context_alive(false);
+        program_cache::set_directory(None);
+
+        let leaked = resource_tracker::snapshot();
+        if !leaked.get_resources().is_empty() {
+            warn!(
+                "GPU resources still alive at shutdown:\n{}",
+                leaked.report()
+            );
+        }
+    }
+}
+
+// Fields drop in declaration order, so everything holding GL objects has to
+// come before the context itself.
+pub struct Graphics {
+    sprite_batch: SpriteBatch<DEFAULT_BATCH_SIZE>,
+    shader_cache: ShaderCache,
+    hot_reload: Option<HotReload>,
+    camera: CameraBlock,
+    camera_buffer: UniformBuffer<CameraBlock>,
+    camera_dirty: bool,
+    viewport: (i32, i32, i32, i32),
+    _debug_output: Option<DebugOutput>,
+    _gl_context: OwnedContext,
+}
+
+impl Graphics {
+    pub(crate) fn new(
+        gl_context: GLContext,
+        config: &RlibConfig,
+        width: u32,
+        height: u32,
+    ) -> Result<Graphics, RlibError> {
+        let gl_context = OwnedContext {
+            _context: gl_context,
+        };
+        deletion_queue::set_context_alive(true);
+
+        let debug_output = if config.gl_debug {
+            DebugOutput::install(config.gl_debug_min_severity, &config.gl_debug_ignored_ids)
+        } else {
+            None
+        };
+
+        // Before anything builds a shader program, the sprite batch included.
+        program_cache::set_directory(config.program_cache_dir.as_deref().map(Path::new));
+
+        let hot_reload = if config.hot_reload {
+            match HotReload::new() {
+                Ok(hot_reload) => Some(hot_reload),
+                Err(e) => {
+                    warn!("Hot reload is unavailable: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let camera = CameraBlock::default();
+        let camera_buffer = UniformBuffer::new(&camera)?;
+        camera_buffer.set_label("Camera");
+
+        Ok(Graphics {
+            sprite_batch: SpriteBatch::new()?,
+            shader_cache: ShaderCache::new(&config.shader_root),
+            hot_reload,
+            camera,
+            camera_buffer,
+            camera_dirty: true,
+            viewport: (0, 0, width as i32, height as i32),
+            _debug_output: debug_output,
+            _gl_context: gl_context,
+        })
+    }
+
+    pub fn clear(&self, color: Color) {
+        unsafe {
+            gl::ClearColor(color.r, color.g, color.b, color.a);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        }
+    }
+
+    pub fn set_alpha_blending(&self, enabled: bool) {
+        state_cache::set_blend(enabled);
+
+        if enabled {
+            state_cache::set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+
+    pub fn set_depth_test(&self, enabled: bool) {
+        state_cache::set_depth_test(enabled);
+    }
+
+    pub fn set_scissor_test(&self, enabled: bool) {
+        state_cache::set_scissor_test(enabled);
+    }
+
+    // Raw GL inside `f` is fine; the state cache forgets everything afterwards
+    // so the next tracked call always reaches the driver.
+    pub fn with_raw_gl<R, F: FnOnce() -> R>(&self, f: F) -> R {
+        let result = f();
+        state_cache::invalidate();
+        result
+    }
+
+    pub fn get_state_cache_stats(&self) -> StateCacheStats {
+        state_cache::get_stats()
+    }
+
+    // Totals across every `StreamBuffer`, including the sprite batch's.
+    pub fn get_stream_buffer_stats(&self) -> StreamBufferStats {
+        stream_buffer::get_stats()
+    }
+
+    pub fn get_program_cache_stats(&self) -> ProgramCacheStats {
+        program_cache::get_stats()
+    }
+
+    pub fn get_viewport(&self) -> (i32, i32, i32, i32) {
+        self.viewport
+    }
+
+    pub fn set_viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.viewport = (x, y, width, height);
+        state_cache::set_viewport(x, y, width, height);
+    }
+
+    pub fn get_view(&self) -> Mat4 {
+        self.camera.view
+    }
+
+    pub fn set_view(&mut self, view: Mat4) {
+        self.camera.view = view;
+        self.camera_dirty = true;
+    }
+
+    pub fn get_projection(&self) -> Mat4 {
+        self.camera.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Mat4) {
+        self.camera.projection = projection;
+        self.camera_dirty = true;
+    }
+
+    // Shared by every program that declares the `Camera` block.
+    pub fn get_camera(&self) -> &CameraBlock {
+        &self.camera
+    }
+
+    // Uploads the camera if it changed since the last upload. Happens on its
+    // own before `render` and when the sprite batch is handed out, so this is
+    // only needed before drawing with raw GL or other programs after
+    // `set_view` or `set_projection`.
+    pub fn apply_camera(&mut self) {
+        if self.camera_dirty {
+            self.camera_buffer.set(&self.camera);
+            self.camera_dirty = false;
+        }
+
+        self.camera_buffer.bind_to(CAMERA_BLOCK_BINDING);
+    }
+
+    pub(crate) fn begin_frame(&mut self, elapsed: f32) {
+        let (_, _, width, height) = self.viewport;
+        self.camera.viewport_size = Vec2::new(width as f32, height as f32);
+        self.camera.time = elapsed;
+        self.camera_dirty = true;
+
+        self.apply_camera();
+    }
+
+    pub fn get_shader_cache(&mut self) -> &mut ShaderCache {
+        &mut self.shader_cache
+    }
+
+    // Both are no-ops unless `hot_reload` is enabled in the config. Programs
+    // loaded through the shader cache are watched automatically.
+    pub fn watch_texture(&mut self, texture: &Texture) {
+        if let Some(hot_reload) = self.hot_reload.as_mut() {
+            hot_reload.watch_texture(texture);
+        }
+    }
+
+    pub fn watch_shader_program(&mut self, program: &ShaderProgram) {
+        if let Some(hot_reload) = self.hot_reload.as_mut() {
+            hot_reload.watch_shader_program(program);
+        }
+    }
+
+    pub fn is_hot_reload_enabled(&self) -> bool {
+        self.hot_reload.is_some()
+    }
+
+    pub(crate) fn poll_hot_reload(&mut self) {
+        let new_programs = self.shader_cache.take_new_programs();
+
+        if let Some(hot_reload) = self.hot_reload.as_mut() {
+            for program in new_programs.iter() {
+                hot_reload.watch_shader_program(program);
+            }
+
+            hot_reload.poll();
+        }
+    }
+
+    pub fn get_sprite_batch(&mut self) -> &mut SpriteBatch<DEFAULT_BATCH_SIZE> {
+        self.apply_camera();
+        &mut self.sprite_batch
+    }
+}
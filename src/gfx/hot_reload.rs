@@ -0,0 +1,237 @@
+use super::shader::{NativeShaderProgram, ShaderProgram};
+use super::texture::{NativeTexture, Texture};
+use crate::error::RlibError;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+#[cfg(target_os = "linux")]
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+
+extern crate log;
+
+// Weak, so watching an asset doesn't keep it alive.
+enum WatchedAsset {
+    Texture(Weak<NativeTexture>),
+    ShaderProgram(Weak<NativeShaderProgram>),
+}
+
+struct Watched {
+    asset: WatchedAsset,
+    files: Vec<PathBuf>,
+}
+
+// Watches the directories of loaded assets rather than the files themselves,
+// since editors often save by writing a new file and renaming it over the old
+// one, which would silently end a watch on the file.
+pub struct HotReload {
+    #[cfg(target_os = "linux")]
+    inotify: Inotify,
+    #[cfg(target_os = "linux")]
+    directories: HashMap<WatchDescriptor, PathBuf>,
+    #[cfg(target_os = "linux")]
+    buffer: Vec<u8>,
+    watched_directories: HashSet<PathBuf>,
+    watched: Vec<Watched>,
+}
+
+impl HotReload {
+    #[cfg(target_os = "linux")]
+    pub fn new() -> Result<HotReload, RlibError> {
+        Ok(HotReload {
+            inotify: Inotify::init()?,
+            directories: HashMap::new(),
+            buffer: vec![0; 4096],
+            watched_directories: HashSet::new(),
+            watched: Vec::new(),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new() -> Result<HotReload, RlibError> {
+        Err(RlibError::Io {
+            path: None,
+            source: std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "hot reload is only supported on Linux",
+            ),
+        })
+    }
+
+    // Textures not loaded from a file are ignored.
+    pub fn watch_texture(&mut self, texture: &Texture) {
+        let path = match texture.get_path() {
+            Some(path) => PathBuf::from(path),
+            None => return,
+        };
+
+        let asset = Rc::downgrade(&texture.handle);
+        let already_watched = self.watched.iter().any(|watched| match &watched.asset {
+            WatchedAsset::Texture(existing) => existing.ptr_eq(&asset),
+            _ => false,
+        });
+
+        if !already_watched {
+            self.add(WatchedAsset::Texture(asset), vec![path]);
+        }
+    }
+
+    // Programs compiled from memory have no files and are ignored.
+    pub fn watch_shader_program(&mut self, program: &ShaderProgram) {
+        let asset = Rc::downgrade(&program.handle);
+        let already_watched = self.watched.iter().any(|watched| match &watched.asset {
+            WatchedAsset::ShaderProgram(existing) => existing.ptr_eq(&asset),
+            _ => false,
+        });
+
+        if !already_watched {
+            let files = program.get_source_files();
+
+            if !files.is_empty() {
+                self.add(WatchedAsset::ShaderProgram(asset), files);
+            }
+        }
+    }
+
+    fn add(&mut self, asset: WatchedAsset, files: Vec<PathBuf>) {
+        for file in files.iter() {
+            self.watch_directory(file);
+        }
+
+        self.watched.push(Watched { asset, files });
+    }
+
+    fn watch_directory(&mut self, file: &Path) {
+        let directory = file.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+
+        if self.watched_directories.contains(&directory) {
+            return;
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let watch_path = if directory.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                directory.as_path()
+            };
+            let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO;
+
+            match self.inotify.add_watch(watch_path, mask) {
+                Ok(descriptor) => {
+                    self.directories.insert(descriptor, directory.clone());
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to watch {} for changes: {}",
+                        watch_path.display(),
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+
+        self.watched_directories.insert(directory);
+    }
+
+    // Reloads everything whose files changed since the last poll. Called by
+    // the main loop once per frame.
+    pub(crate) fn poll(&mut self) {
+        let changed = self.read_changes();
+
+        if changed.is_empty() {
+            return;
+        }
+
+        self.watched.retain(|watched| match &watched.asset {
+            WatchedAsset::Texture(texture) => texture.strong_count() > 0,
+            WatchedAsset::ShaderProgram(program) => program.strong_count() > 0,
+        });
+
+        let mut new_files = Vec::new();
+
+        for watched in self.watched.iter_mut() {
+            if !watched.files.iter().any(|file| changed.contains(file)) {
+                continue;
+            }
+
+            match &watched.asset {
+                WatchedAsset::Texture(texture) => {
+                    if let Some(handle) = texture.upgrade() {
+                        let texture = Texture { handle };
+                        let path = texture.get_path().unwrap_or_default();
+
+                        match texture.reload() {
+                            Ok(()) => info!("Reloaded texture {}", path),
+                            Err(e) => error!("Failed to reload texture {}: {}", path, e),
+                        }
+                    }
+                }
+                WatchedAsset::ShaderProgram(program) => {
+                    if let Some(handle) = program.upgrade() {
+                        let program = ShaderProgram { handle };
+                        let name = watched.files[0].display().to_string();
+
+                        match program.reload() {
+                            Ok(()) => {
+                                info!("Reloaded shader program {}", name);
+
+                                // Includes may have been added or removed.
+                                watched.files = program.get_source_files();
+                                new_files.extend(watched.files.iter().cloned());
+                            }
+                            Err(e) => error!(
+                                "Failed to reload shader program {}, keeping the old one: {}",
+                                name, e
+                            ),
+                        }
+                    }
+                }
+            }
+        }
+
+        for file in new_files.iter() {
+            self.watch_directory(file);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read_changes(&mut self) -> HashSet<PathBuf> {
+        let mut changed = HashSet::new();
+
+        loop {
+            let events = match self.inotify.read_events(&mut self.buffer) {
+                Ok(events) => events,
+                Err(e) => {
+                    error!("Failed to read file change events: {}", e);
+                    break;
+                }
+            };
+
+            let mut any = false;
+
+            for event in events {
+                any = true;
+
+                if let (Some(directory), Some(name)) = (self.directories.get(&event.wd), event.name)
+                {
+                    changed.insert(directory.join(name));
+                }
+            }
+
+            if !any {
+                break;
+            }
+        }
+
+        changed
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read_changes(&mut self) -> HashSet<PathBuf> {
+        HashSet::new()
+    }
+}
@@ -1,6 +1,19 @@
 pub mod buffer;
 pub mod color;
+pub mod debug;
+pub(crate) mod deletion_queue;
+pub mod graphics;
+pub mod hot_reload;
+pub mod program_cache;
+pub mod resource_tracker;
 pub mod shader;
+pub mod shader_cache;
+pub mod shader_preprocessor;
 pub mod sprite_batch;
+pub mod state_cache;
+pub mod stream_buffer;
 pub mod texture;
 pub mod texture_region;
+pub mod uniform;
+pub mod uniform_buffer;
+pub mod vertex_array;
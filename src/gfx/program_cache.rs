@@ -0,0 +1,423 @@
+use super::shader_preprocessor::ShaderDefines;
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::path::{Path, PathBuf};
+
+extern crate log;
+
+const FILE_MAGIC: &[u8; 4] = b"RLPB";
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ProgramCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // Binaries the driver refused, usually after an update it didn't reflect
+    // in its version strings.
+    pub rejected: u64,
+    pub stored: u64,
+}
+
+struct ProgramCache {
+    directory: PathBuf,
+    // Hash of the driver strings, the starting point of every key.
+    driver_hash: u64,
+}
+
+// Reachable from `ShaderProgram` constructors without threading a handle
+// through every caller, like the rest of the per-context GL bookkeeping.
+thread_local! {
+    static CACHE: RefCell<Option<ProgramCache>> = const { RefCell::new(None) };
+    static STATS: RefCell<ProgramCacheStats> = RefCell::new(ProgramCacheStats::default());
+}
+
+// Needs a current context. `None`, or a driver without binary formats,
+// disables the cache.
+pub(crate) fn set_directory(directory: Option<&Path>) {
+    let cache = directory.and_then(|directory| {
+        let mut format_count: i32 = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut format_count);
+        }
+
+        if format_count == 0 {
+            warn!("The driver supports no program binary formats, not caching shader programs");
+            return None;
+        }
+
+        if let Err(e) = std::fs::create_dir_all(directory) {
+            warn!(
+                "Can't create program cache directory {}: {}",
+                directory.display(),
+                e
+            );
+            return None;
+        }
+
+        let mut hasher = Fnv1a::new();
+        for name in [gl::VENDOR, gl::RENDERER, gl::VERSION] {
+            hasher.write_str(&get_gl_string(name));
+        }
+
+        Some(ProgramCache {
+            directory: directory.to_path_buf(),
+            driver_hash: hasher.finish(),
+        })
+    });
+
+    CACHE.with(|current| *current.borrow_mut() = cache);
+}
+
+pub(crate) fn is_enabled() -> bool {
+    CACHE.with(|cache| cache.borrow().is_some())
+}
+
+// Identifies a program by its stages' types and final sources, the defines
+// they were built with and the driver. `None` while the cache is disabled.
+pub(crate) fn get_key(stages: &[(u32, &str)], defines: &[&ShaderDefines]) -> Option<u64> {
+    let driver_hash = CACHE.with(|cache| cache.borrow().as_ref().map(|cache| cache.driver_hash))?;
+
+    Some(hash_key(driver_hash, stages, defines))
+}
+
+fn hash_key(driver_hash: u64, stages: &[(u32, &str)], defines: &[&ShaderDefines]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(&driver_hash.to_le_bytes());
+
+    for (shader_type, source) in stages {
+        hasher.write(&shader_type.to_le_bytes());
+        hasher.write_str(source);
+    }
+
+    for stage_defines in defines {
+        for (name, value) in stage_defines.iter() {
+            hasher.write_str(name);
+            hasher.write_str(value);
+        }
+    }
+
+    hasher.finish()
+}
+
+// A linked program from the cached binary, or `None` on a miss or when the
+// driver rejects it, in which case the caller builds from source.
+pub(crate) fn load(key: u64) -> Option<u32> {
+    let path = get_path(key)?;
+
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(_) => {
+            STATS.with(|stats| stats.borrow_mut().misses += 1);
+            return None;
+        }
+    };
+
+    if data.len() < 8 || &data[..4] != FILE_MAGIC {
+        reject(&path, "malformed cache file");
+        return None;
+    }
+
+    let format = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let binary = &data[8..];
+
+    let handle = unsafe { gl::CreateProgram() };
+    if handle == 0 {
+        return None;
+    }
+
+    let mut status: i32 = 0;
+    unsafe {
+        gl::ProgramBinary(
+            handle,
+            format,
+            binary.as_ptr() as *const std::ffi::c_void,
+            binary.len() as i32,
+        );
+        gl::GetProgramiv(handle, gl::LINK_STATUS, &mut status);
+    }
+
+    if status == 0 {
+        unsafe {
+            gl::DeleteProgram(handle);
+        }
+        reject(&path, "binary rejected by the driver");
+        return None;
+    }
+
+    STATS.with(|stats| stats.borrow_mut().hits += 1);
+
+    Some(handle)
+}
+
+// `program` must have been linked with `PROGRAM_BINARY_RETRIEVABLE_HINT` set.
+pub(crate) fn store(key: u64, program: u32) {
+    let path = match get_path(key) {
+        Some(path) => path,
+        None => return,
+    };
+
+    let mut length: i32 = 0;
+    unsafe {
+        gl::GetProgramiv(program, gl::PROGRAM_BINARY_LENGTH, &mut length);
+    }
+
+    if length <= 0 {
+        return;
+    }
+
+    let mut binary = vec![0u8; length as usize];
+    let mut written: i32 = 0;
+    let mut format: u32 = 0;
+    unsafe {
+        gl::GetProgramBinary(
+            program,
+            length,
+            &mut written,
+            &mut format,
+            binary.as_mut_ptr() as *mut std::ffi::c_void,
+        );
+    }
+    binary.truncate(written.max(0) as usize);
+
+    let mut data = Vec::with_capacity(binary.len() + 8);
+    data.extend_from_slice(FILE_MAGIC);
+    data.extend_from_slice(&format.to_le_bytes());
+    data.extend_from_slice(&binary);
+
+    // Written aside and renamed, so a crash never leaves a truncated entry.
+    let temp_path = path.with_extension("tmp");
+    let result = std::fs::write(&temp_path, &data).and_then(|_| std::fs::rename(&temp_path, &path));
+
+    match result {
+        Ok(()) => STATS.with(|stats| stats.borrow_mut().stored += 1),
+        Err(e) => warn!("Failed to write program binary {}: {}", path.display(), e),
+    }
+}
+
+pub fn get_stats() -> ProgramCacheStats {
+    STATS.with(|stats| *stats.borrow())
+}
+
+pub fn reset_stats() {
+    STATS.with(|stats| *stats.borrow_mut() = ProgramCacheStats::default());
+}
+
+fn get_path(key: u64) -> Option<PathBuf> {
+    CACHE.with(|cache| {
+        cache
+            .borrow()
+            .as_ref()
+            .map(|cache| cache.directory.join(format!("{:016x}.bin", key)))
+    })
+}
+
+// The entry is removed so the rebuilt program's binary replaces it.
+fn reject(path: &Path, reason: &str) {
+    debug!(
+        "Ignoring cached program binary {}: {}",
+        path.display(),
+        reason
+    );
+    let _ = std::fs::remove_file(path);
+
+    STATS.with(|stats| {
+        let mut stats = stats.borrow_mut();
+        stats.rejected += 1;
+        stats.misses += 1;
+    });
+}
+
+fn get_gl_string(name: u32) -> String {
+    unsafe {
+        let string = gl::GetString(name);
+
+        if string.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(string as *const std::os::raw::c_char)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+}
+
+// 64-bit FNV-1a; stable across runs and builds, unlike `DefaultHasher`.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(FNV_OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    // Length-prefixed, so adjacent strings can't run into each other.
+    fn write_str(&mut self, string: &str) {
+        self.write(&(string.len() as u64).to_le_bytes());
+        self.write(string.as_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const VERTEX: &str = "void main() { gl_Position = vec4(0.0); }";
+    const FRAGMENT: &str = "out vec4 color; void main() { color = vec4(1.0); }";
+
+    fn key(stages: &[(u32, &str)], defines: &[&ShaderDefines]) -> u64 {
+        hash_key(0x1234, stages, defines)
+    }
+
+    // Points the cache at a fresh directory for this thread only.
+    struct TempCache {
+        _directory: TempDir,
+    }
+
+    impl TempCache {
+        fn new(name: &str) -> TempCache {
+            let directory = TempDir::new(&format!("program-cache-{}", name));
+
+            CACHE.with(|cache| {
+                *cache.borrow_mut() = Some(ProgramCache {
+                    directory: directory.get_path().to_path_buf(),
+                    driver_hash: 0x1234,
+                })
+            });
+            reset_stats();
+
+            TempCache {
+                _directory: directory,
+            }
+        }
+    }
+
+    impl Drop for TempCache {
+        fn drop(&mut self) {
+            CACHE.with(|cache| *cache.borrow_mut() = None);
+        }
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_vectors() {
+        assert_eq!(Fnv1a::new().finish(), 0xcbf2_9ce4_8422_2325);
+
+        let mut hasher = Fnv1a::new();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn key_is_stable() {
+        let defines = ShaderDefines::new().with("LIGHTS", "4");
+        let stages = [(gl::VERTEX_SHADER, VERTEX), (gl::FRAGMENT_SHADER, FRAGMENT)];
+
+        assert_eq!(key(&stages, &[&defines]), key(&stages, &[&defines]));
+        assert_eq!(
+            key(&stages, &[&defines]),
+            key(&stages, &[&ShaderDefines::new().with("LIGHTS", "4")])
+        );
+    }
+
+    #[test]
+    fn key_depends_on_stage_order() {
+        let forward = [(gl::VERTEX_SHADER, VERTEX), (gl::FRAGMENT_SHADER, FRAGMENT)];
+        let backward = [(gl::FRAGMENT_SHADER, FRAGMENT), (gl::VERTEX_SHADER, VERTEX)];
+
+        assert_ne!(key(&forward, &[]), key(&backward, &[]));
+    }
+
+    #[test]
+    fn key_covers_types_sources_defines_and_driver() {
+        let stages = [(gl::VERTEX_SHADER, VERTEX), (gl::FRAGMENT_SHADER, FRAGMENT)];
+        let base = key(&stages, &[]);
+
+        assert_ne!(
+            base,
+            key(
+                &[(gl::VERTEX_SHADER, VERTEX), (gl::GEOMETRY_SHADER, FRAGMENT)],
+                &[]
+            )
+        );
+        assert_ne!(
+            base,
+            key(
+                &[(gl::VERTEX_SHADER, VERTEX), (gl::FRAGMENT_SHADER, VERTEX)],
+                &[]
+            )
+        );
+        assert_ne!(
+            base,
+            key(&stages, &[&ShaderDefines::new().with_flag("FOG")])
+        );
+        assert_ne!(
+            key(&stages, &[&ShaderDefines::new().with("LIGHTS", "4")]),
+            key(&stages, &[&ShaderDefines::new().with("LIGHTS", "8")])
+        );
+        assert_ne!(base, hash_key(0x5678, &stages, &[]));
+    }
+
+    #[test]
+    fn key_keeps_adjacent_sources_apart() {
+        let split = [(gl::VERTEX_SHADER, "ab"), (gl::VERTEX_SHADER, "c")];
+        let shifted = [(gl::VERTEX_SHADER, "a"), (gl::VERTEX_SHADER, "bc")];
+
+        assert_ne!(key(&split, &[]), key(&shifted, &[]));
+    }
+
+    #[test]
+    fn get_key_needs_an_enabled_cache() {
+        let stages = [(gl::VERTEX_SHADER, VERTEX)];
+        assert_eq!(get_key(&stages, &[]), None);
+
+        let _cache = TempCache::new("key");
+        assert_eq!(get_key(&stages, &[]), Some(key(&stages, &[])));
+    }
+
+    #[test]
+    fn load_counts_missing_files_as_misses() {
+        let _cache = TempCache::new("missing");
+
+        assert_eq!(load(1), None);
+
+        let stats = get_stats();
+        assert_eq!((stats.hits, stats.misses, stats.rejected), (0, 1, 0));
+    }
+
+    #[test]
+    fn load_rejects_truncated_files() {
+        let _cache = TempCache::new("truncated");
+        let path = get_path(2).unwrap();
+        std::fs::write(&path, b"RLPB\x01").unwrap();
+
+        assert_eq!(load(2), None);
+        assert!(!path.exists());
+
+        let stats = get_stats();
+        assert_eq!((stats.hits, stats.misses, stats.rejected), (0, 1, 1));
+    }
+
+    #[test]
+    fn load_rejects_bad_magic() {
+        let _cache = TempCache::new("magic");
+        let path = get_path(3).unwrap();
+        std::fs::write(&path, b"XXXX\x01\x00\x00\x00binary").unwrap();
+
+        assert_eq!(load(3), None);
+        assert!(!path.exists());
+
+        let stats = get_stats();
+        assert_eq!((stats.hits, stats.misses, stats.rejected), (0, 1, 1));
+    }
+}
@@ -0,0 +1,410 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::panic::Location;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ResourceKind {
+    Texture,
+    Buffer,
+    ShaderProgram,
+    VertexArray,
+}
+
+impl ResourceKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ResourceKind::Texture => "texture",
+            ResourceKind::Buffer => "buffer",
+            ResourceKind::ShaderProgram => "shader program",
+            ResourceKind::VertexArray => "vertex array",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ResourceInfo {
+    pub id: u64,
+    pub kind: ResourceKind,
+    pub handle: u32,
+    pub label: Option<String>,
+    pub dimensions: Option<(u32, u32, u32)>,
+    pub mip_levels: u32,
+    pub byte_size: usize,
+    pub created_at: &'static Location<'static>,
+}
+
+#[derive(Clone, Default)]
+pub struct ResourceSnapshot {
+    resources: Vec<ResourceInfo>,
+}
+
+#[derive(Clone, Default)]
+pub struct ResourceDelta {
+    pub created: Vec<ResourceInfo>,
+    pub destroyed: Vec<ResourceInfo>,
+}
+
+struct Registry {
+    next_id: u64,
+    live: BTreeMap<(ResourceKind, u32), ResourceInfo>,
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = const {
+        RefCell::new(Registry {
+            next_id: 0,
+            live: BTreeMap::new(),
+        })
+    };
+}
+
+pub(crate) fn register(
+    kind: ResourceKind,
+    handle: u32,
+    dimensions: Option<(u32, u32, u32)>,
+    mip_levels: u32,
+    byte_size: usize,
+    created_at: &'static Location<'static>,
+) {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let id = registry.next_id;
+        registry.next_id += 1;
+
+        registry.live.insert(
+            (kind, handle),
+            ResourceInfo {
+                id,
+                kind,
+                handle,
+                label: None,
+                dimensions,
+                mip_levels,
+                byte_size,
+                created_at,
+            },
+        );
+    });
+}
+
+pub(crate) fn unregister(kind: ResourceKind, handle: u32) {
+    REGISTRY.with(|registry| registry.borrow_mut().live.remove(&(kind, handle)));
+}
+
+pub(crate) fn update<F: FnOnce(&mut ResourceInfo)>(kind: ResourceKind, handle: u32, f: F) {
+    REGISTRY.with(|registry| {
+        if let Some(info) = registry.borrow_mut().live.get_mut(&(kind, handle)) {
+            f(info);
+        }
+    });
+}
+
+// Moves an entry over to a new GL name, returning its label so the caller
+// can reapply it to the new object.
+pub(crate) fn rehandle(kind: ResourceKind, old_handle: u32, new_handle: u32) -> Option<String> {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let mut info = registry.live.remove(&(kind, old_handle))?;
+        info.handle = new_handle;
+        let label = info.label.clone();
+        registry.live.insert((kind, new_handle), info);
+
+        label
+    })
+}
+
+// Trades id, label and creation site between two entries, for objects that
+// were rebuilt in place and should keep reporting as the original. Returns
+// the label now belonging to `handle_b`.
+pub(crate) fn swap_identity(kind: ResourceKind, handle_a: u32, handle_b: u32) -> Option<String> {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let mut a = registry.live.remove(&(kind, handle_a))?;
+        let mut b = match registry.live.remove(&(kind, handle_b)) {
+            Some(b) => b,
+            None => {
+                registry.live.insert((kind, handle_a), a);
+                return None;
+            }
+        };
+
+        std::mem::swap(&mut a.id, &mut b.id);
+        std::mem::swap(&mut a.label, &mut b.label);
+        std::mem::swap(&mut a.created_at, &mut b.created_at);
+        let label = b.label.clone();
+
+        registry.live.insert((kind, handle_a), a);
+        registry.live.insert((kind, handle_b), b);
+
+        label
+    })
+}
+
+pub(crate) fn set_label(kind: ResourceKind, handle: u32, label: &str) {
+    update(kind, handle, |info| info.label = Some(label.to_string()));
+}
+
+// Bytes used by an RGBA8 texture including its mip chain.
+pub(crate) fn texture_byte_size(width: u32, height: u32, depth: u32, mip_levels: u32) -> usize {
+    let mut total = 0;
+    let (mut width, mut height) = (width as usize, height as usize);
+
+    for _ in 0..mip_levels.max(1) {
+        total += width * height * depth as usize * 4;
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+    }
+
+    total
+}
+
+pub fn snapshot() -> ResourceSnapshot {
+    REGISTRY.with(|registry| ResourceSnapshot {
+        resources: registry.borrow().live.values().cloned().collect(),
+    })
+}
+
+impl ResourceSnapshot {
+    pub fn get_resources(&self) -> &[ResourceInfo] {
+        &self.resources
+    }
+
+    pub fn get_count(&self, kind: ResourceKind) -> usize {
+        self.resources.iter().filter(|r| r.kind == kind).count()
+    }
+
+    pub fn get_byte_size(&self, kind: ResourceKind) -> usize {
+        self.resources
+            .iter()
+            .filter(|r| r.kind == kind)
+            .map(|r| r.byte_size)
+            .sum()
+    }
+
+    pub fn get_total_byte_size(&self) -> usize {
+        self.resources.iter().map(|r| r.byte_size).sum()
+    }
+
+    pub fn diff(&self, previous: &ResourceSnapshot) -> ResourceDelta {
+        let is_in = |resources: &[ResourceInfo], id: u64| resources.iter().any(|r| r.id == id);
+
+        ResourceDelta {
+            created: self
+                .resources
+                .iter()
+                .filter(|r| !is_in(&previous.resources, r.id))
+                .cloned()
+                .collect(),
+            destroyed: previous
+                .resources
+                .iter()
+                .filter(|r| !is_in(&self.resources, r.id))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let kinds = [
+            ResourceKind::Texture,
+            ResourceKind::Buffer,
+            ResourceKind::ShaderProgram,
+            ResourceKind::VertexArray,
+        ];
+
+        let _ = writeln!(
+            report,
+            "{} live GPU resources, {} bytes",
+            self.resources.len(),
+            self.get_total_byte_size()
+        );
+
+        for kind in kinds {
+            let _ = writeln!(
+                report,
+                "  {}: {} ({} bytes)",
+                kind.name(),
+                self.get_count(kind),
+                self.get_byte_size(kind)
+            );
+        }
+
+        for resource in self.resources.iter() {
+            let _ = writeln!(report, "  {}", describe(resource));
+        }
+
+        report
+    }
+}
+
+impl ResourceDelta {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.destroyed.is_empty()
+    }
+
+    pub fn log(&self) {
+        for resource in self.created.iter() {
+            debug!("Created {}", describe(resource));
+        }
+
+        for resource in self.destroyed.iter() {
+            debug!("Destroyed {}", describe(resource));
+        }
+    }
+}
+
+fn describe(resource: &ResourceInfo) -> String {
+    let mut description = format!("{} {}", resource.kind.name(), resource.handle);
+
+    if let Some(label) = &resource.label {
+        let _ = write!(description, " \"{}\"", label);
+    }
+
+    if let Some((width, height, depth)) = resource.dimensions {
+        let _ = write!(
+            description,
+            " {}x{}x{}, {} mips",
+            width, height, depth, resource.mip_levels
+        );
+    }
+
+    let _ = write!(
+        description,
+        ", {} bytes, created at {}",
+        resource.byte_size, resource.created_at
+    );
+
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(id: u64, kind: ResourceKind, handle: u32, byte_size: usize) -> ResourceInfo {
+        ResourceInfo {
+            id,
+            kind,
+            handle,
+            label: None,
+            dimensions: None,
+            mip_levels: 0,
+            byte_size,
+            created_at: Location::caller(),
+        }
+    }
+
+    fn ids(resources: &[ResourceInfo]) -> Vec<u64> {
+        resources.iter().map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn diff_matches_by_id() {
+        let previous = ResourceSnapshot {
+            resources: vec![
+                resource(0, ResourceKind::Texture, 1, 64),
+                resource(1, ResourceKind::Buffer, 1, 32),
+            ],
+        };
+        let current = ResourceSnapshot {
+            resources: vec![
+                resource(1, ResourceKind::Buffer, 1, 32),
+                resource(2, ResourceKind::Buffer, 2, 16),
+            ],
+        };
+
+        let delta = current.diff(&previous);
+        assert_eq!(ids(&delta.created), [2]);
+        assert_eq!(ids(&delta.destroyed), [0]);
+        assert!(current.diff(&current).is_empty());
+
+        // A reused GL name is still a different resource.
+        let reused = ResourceSnapshot {
+            resources: vec![resource(3, ResourceKind::Texture, 1, 64)],
+        };
+        let delta = reused.diff(&previous);
+        assert_eq!(ids(&delta.created), [3]);
+        assert_eq!(ids(&delta.destroyed), [0, 1]);
+    }
+
+    #[test]
+    fn reallocated_handles_are_not_leaks() {
+        let created_at = Location::caller();
+        register(ResourceKind::Buffer, 5, None, 0, 128, created_at);
+        set_label(ResourceKind::Buffer, 5, "vertices");
+        let before = snapshot();
+
+        assert_eq!(
+            rehandle(ResourceKind::Buffer, 5, 9).as_deref(),
+            Some("vertices")
+        );
+        update(ResourceKind::Buffer, 9, |info| info.byte_size = 256);
+
+        let after = snapshot();
+        assert!(after.diff(&before).is_empty());
+        assert_eq!(after.get_byte_size(ResourceKind::Buffer), 256);
+        assert_eq!(after.get_resources()[0].handle, 9);
+
+        unregister(ResourceKind::Buffer, 9);
+    }
+
+    #[test]
+    fn reloaded_programs_keep_their_identity() {
+        let created_at = Location::caller();
+        register(ResourceKind::ShaderProgram, 1, None, 0, 0, created_at);
+        set_label(ResourceKind::ShaderProgram, 1, "sprite");
+        let before = snapshot();
+
+        // Reload links a fresh program, swaps it in, then drops the old one.
+        register(ResourceKind::ShaderProgram, 2, None, 0, 0, created_at);
+        let label = swap_identity(ResourceKind::ShaderProgram, 2, 1);
+        assert_eq!(label, None);
+        unregister(ResourceKind::ShaderProgram, 1);
+
+        let after = snapshot();
+        assert!(after.diff(&before).is_empty());
+        assert_eq!(after.get_resources()[0].handle, 2);
+        assert_eq!(after.get_resources()[0].label.as_deref(), Some("sprite"));
+
+        unregister(ResourceKind::ShaderProgram, 2);
+        let delta = snapshot().diff(&before);
+        assert_eq!(ids(&delta.destroyed), ids(before.get_resources()));
+    }
+
+    #[test]
+    fn report_lists_totals_and_resources() {
+        let mut texture = resource(0, ResourceKind::Texture, 3, 80);
+        texture.label = Some("atlas".to_string());
+        texture.dimensions = Some((4, 4, 1));
+        texture.mip_levels = 3;
+        let buffer = resource(1, ResourceKind::Buffer, 7, 48);
+
+        let report = ResourceSnapshot {
+            resources: vec![texture.clone(), buffer.clone()],
+        }
+        .report();
+
+        let expected = format!(
+            concat!(
+                "2 live GPU resources, 128 bytes\n",
+                "  texture: 1 (80 bytes)\n",
+                "  buffer: 1 (48 bytes)\n",
+                "  shader program: 0 (0 bytes)\n",
+                "  vertex array: 0 (0 bytes)\n",
+                "  texture 3 \"atlas\" 4x4x1, 3 mips, 80 bytes, created at {}\n",
+                "  buffer 7, 48 bytes, created at {}\n",
+            ),
+            texture.created_at, buffer.created_at
+        );
+        assert_eq!(report, expected);
+    }
+
+    #[test]
+    fn texture_sizes_include_the_mip_chain() {
+        assert_eq!(texture_byte_size(4, 4, 1, 1), 64);
+        assert_eq!(texture_byte_size(4, 4, 1, 3), (16 + 4 + 1) * 4);
+        assert_eq!(texture_byte_size(4, 1, 1, 3), (4 + 2 + 1) * 4);
+    }
+}
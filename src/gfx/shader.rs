@@ -1,11 +1,79 @@
 extern crate log;
 
+use super::debug;
+use super::deletion_queue::{self, GlObject};
+use super::program_cache;
+use super::resource_tracker::{self, ResourceKind};
+use super::shader_preprocessor::{self, PreprocessedShader, ShaderDefines};
+use super::state_cache;
+use super::uniform::{self, TextureUnit, UniformInfo, UniformValue, Uniforms};
+use super::uniform_buffer::{
+    self, BlockInfo, BlockKind, CameraBlock, CAMERA_BLOCK_BINDING, CAMERA_BLOCK_NAME,
+};
+use crate::error::{self, RlibError};
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::{collections::HashMap, ffi::CString, rc::Rc};
 
+// The stages a `#pragma stage` section can name.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Geometry,
+    TessControl,
+    TessEvaluation,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn to_gl(self) -> u32 {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+            ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+            ShaderStage::TessControl => gl::TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            ShaderStage::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+
+    pub fn from_gl(shader_type: u32) -> Option<ShaderStage> {
+        match shader_type {
+            gl::VERTEX_SHADER => Some(ShaderStage::Vertex),
+            gl::FRAGMENT_SHADER => Some(ShaderStage::Fragment),
+            gl::GEOMETRY_SHADER => Some(ShaderStage::Geometry),
+            gl::TESS_CONTROL_SHADER => Some(ShaderStage::TessControl),
+            gl::TESS_EVALUATION_SHADER => Some(ShaderStage::TessEvaluation),
+            gl::COMPUTE_SHADER => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+}
+
+// Where a shader loaded from disk came from, so it can be rebuilt.
+#[derive(Clone)]
+struct ShaderOrigin {
+    root: PathBuf,
+    path: String,
+    defines: ShaderDefines,
+    // Whether `path` is a file of `#pragma stage` sections.
+    combined: bool,
+}
+
 pub(crate) struct NativeShader {
     handle: u32,
     shader_type: u32,
     source: String,
+    origin: Option<ShaderOrigin>,
+    files: Vec<PathBuf>,
+}
+
+impl Drop for NativeShader {
+    fn drop(&mut self) {
+        deletion_queue::queue_delete(GlObject::Shader(self.handle));
+    }
 }
 
 #[derive(Clone)]
@@ -14,21 +82,29 @@ pub struct Shader {
 }
 
 impl Shader {
-    #[allow(temporary_cstring_as_ptr)]
-    pub fn from_string(shader_type: u32, source: &str) -> Option<Shader> {
+    pub fn from_string(shader_type: u32, source: &str) -> Result<Shader, RlibError> {
+        Shader::compile(shader_type, source, None, Vec::new())
+    }
+
+    fn compile(
+        shader_type: u32,
+        source: &str,
+        origin: Option<ShaderOrigin>,
+        files: Vec<PathBuf>,
+    ) -> Result<Shader, RlibError> {
+        let source_cstr = CString::new(source).map_err(|_| RlibError::ShaderCompile {
+            stage: shader_type,
+            log: "shader source contains a NUL byte".to_string(),
+        })?;
+
         let handle: u32 = unsafe { gl::CreateShader(shader_type) };
 
         if handle == 0 {
-            None
+            Err(RlibError::GlResource("shader".to_string()))
         } else {
             let mut status: i32 = 1;
             unsafe {
-                gl::ShaderSource(
-                    handle,
-                    1,
-                    &CString::new(source).unwrap().as_ptr(),
-                    std::ptr::null(),
-                );
+                gl::ShaderSource(handle, 1, &source_cstr.as_ptr(), std::ptr::null());
                 gl::CompileShader(handle);
                 gl::GetShaderiv(handle, gl::COMPILE_STATUS, &mut status);
             }
@@ -54,25 +130,105 @@ impl Shader {
                     );
                 }
 
-                error!("Error compiling shader: {}", error.to_string_lossy());
-
                 unsafe {
                     gl::DeleteShader(handle);
                 }
 
-                return None;
+                return Err(RlibError::ShaderCompile {
+                    stage: shader_type,
+                    log: error.to_string_lossy().into_owned(),
+                });
             }
 
-            Some(Shader {
+            Ok(Shader {
                 handle: Rc::new(NativeShader {
                     handle,
                     shader_type,
                     source: source.to_owned(),
+                    origin,
+                    files,
                 }),
             })
         }
     }
 
+    // `path` and its includes are relative to `root`.
+    pub fn from_file(
+        shader_type: u32,
+        root: &Path,
+        path: &str,
+        defines: &ShaderDefines,
+    ) -> Result<Shader, RlibError> {
+        Shader::from_origin(
+            shader_type,
+            ShaderOrigin {
+                root: root.to_path_buf(),
+                path: path.to_string(),
+                defines: defines.clone(),
+                combined: false,
+            },
+        )
+    }
+
+    // One section of a file made of `#pragma stage` sections, as
+    // `ShaderProgram::from_file` compiles it; `get_source` returns just that
+    // stage, preprocessed.
+    pub fn from_stage_file(
+        root: &Path,
+        path: &str,
+        stage: ShaderStage,
+        defines: &ShaderDefines,
+    ) -> Result<Shader, RlibError> {
+        Shader::from_origin(
+            stage.to_gl(),
+            ShaderOrigin {
+                root: root.to_path_buf(),
+                path: path.to_string(),
+                defines: defines.clone(),
+                combined: true,
+            },
+        )
+    }
+
+    fn from_origin(shader_type: u32, origin: ShaderOrigin) -> Result<Shader, RlibError> {
+        ProgramStage::from_origin(shader_type, origin)?.compile()
+    }
+
+    // Runs an in-memory source through the preprocessor, so it can use the
+    // builtin includes. `name` is what compile errors refer to it as.
+    pub fn from_source(
+        shader_type: u32,
+        name: &str,
+        source: &str,
+        defines: &ShaderDefines,
+    ) -> Result<Shader, RlibError> {
+        let shader = shader_preprocessor::preprocess(Path::new(""), name, source, defines)?;
+        Shader::from_preprocessed(shader_type, &shader)
+    }
+
+    pub fn from_preprocessed(
+        shader_type: u32,
+        shader: &PreprocessedShader,
+    ) -> Result<Shader, RlibError> {
+        Shader::compile_preprocessed(shader_type, shader, None)
+    }
+
+    fn compile_preprocessed(
+        shader_type: u32,
+        shader: &PreprocessedShader,
+        origin: Option<ShaderOrigin>,
+    ) -> Result<Shader, RlibError> {
+        Shader::compile(shader_type, &shader.source, origin, shader.paths.clone()).map_err(|e| {
+            match e {
+                RlibError::ShaderCompile { stage, log } => RlibError::ShaderCompile {
+                    stage,
+                    log: shader.map_log(&log),
+                },
+                e => e,
+            }
+        })
+    }
+
     pub fn get_handle(&self) -> u32 {
         self.handle.handle
     }
@@ -84,22 +240,166 @@ impl Shader {
     pub fn get_source(&self) -> &str {
         &self.handle.source
     }
+
+    // Files on disk the source was read from, including includes.
+    pub fn get_files(&self) -> &[PathBuf] {
+        &self.handle.files
+    }
+}
+
+// What a program was built from, kept so it can be rebuilt and looked up in
+// the program cache without holding on to the compiled shaders.
+#[derive(Clone)]
+struct ProgramStage {
+    shader_type: u32,
+    source: PreprocessedShader,
+    origin: Option<ShaderOrigin>,
+}
+
+impl ProgramStage {
+    fn from_shader(shader: &Shader) -> ProgramStage {
+        ProgramStage {
+            shader_type: shader.get_type(),
+            source: PreprocessedShader {
+                source: shader.get_source().to_string(),
+                files: Vec::new(),
+                paths: shader.get_files().to_vec(),
+            },
+            origin: shader.handle.origin.clone(),
+        }
+    }
+
+    fn from_origin(shader_type: u32, origin: ShaderOrigin) -> Result<ProgramStage, RlibError> {
+        let full_path = origin.root.join(&origin.path);
+        let mut source = shader_preprocessor::read_source(&full_path)?;
+
+        if origin.combined {
+            source = shader_preprocessor::split_stages(&origin.path, &source)?
+                .into_iter()
+                .find(|(stage, _)| *stage == shader_type)
+                .map(|(_, stage_source)| stage_source)
+                .ok_or_else(|| {
+                    RlibError::Parse(format!(
+                        "{}: no {} stage",
+                        origin.path,
+                        error::shader_stage_name(shader_type)
+                    ))
+                })?;
+        }
+
+        let mut shader =
+            shader_preprocessor::preprocess(&origin.root, &origin.path, &source, &origin.defines)?;
+        shader.paths.insert(0, full_path);
+
+        Ok(ProgramStage {
+            shader_type,
+            source: shader,
+            origin: Some(origin),
+        })
+    }
+
+    // Read again from its files, or this stage again if it came from memory.
+    fn reread(&self) -> Result<ProgramStage, RlibError> {
+        match &self.origin {
+            Some(origin) => ProgramStage::from_origin(self.shader_type, origin.clone()),
+            None => Ok(self.clone()),
+        }
+    }
+
+    fn compile(&self) -> Result<Shader, RlibError> {
+        Shader::compile_preprocessed(self.shader_type, &self.source, self.origin.clone())
+    }
 }
 
 pub(crate) struct NativeShaderProgram {
-    handle: u32,
-    uniforms: HashMap<String, u32>,
+    // Reloading swaps a freshly linked program in behind every clone.
+    handle: Cell<u32>,
+    uniforms: RefCell<HashMap<String, UniformInfo>>,
+    // Locations of every element of array uniforms, by array name. GL only
+    // makes them consecutive for an explicit `layout(location)`.
+    element_locations: RefCell<HashMap<String, Vec<i32>>>,
+    blocks: RefCell<HashMap<String, BlockInfo>>,
+    // Uniform names already warned about, so a bad setter called every frame
+    // only logs once.
+    reported: RefCell<HashSet<String>>,
+    stages: RefCell<Vec<ProgramStage>>,
+}
+
+impl Drop for NativeShaderProgram {
+    fn drop(&mut self) {
+        resource_tracker::unregister(ResourceKind::ShaderProgram, self.handle.get());
+        deletion_queue::queue_delete(GlObject::Program(self.handle.get()));
+    }
 }
 
 #[derive(Clone)]
 pub struct ShaderProgram {
-    handle: Rc<NativeShaderProgram>,
+    pub(crate) handle: Rc<NativeShaderProgram>,
 }
 
 impl ShaderProgram {
-    pub fn from_shaders(shaders: &[Shader]) -> Option<ShaderProgram> {
+    // Links `shaders`, or loads the program from the program cache when it
+    // is enabled and holds a binary for the same sources.
+    #[track_caller]
+    pub fn from_shaders(shaders: &[Shader]) -> Result<ShaderProgram, RlibError> {
+        let stages = shaders.iter().map(ProgramStage::from_shader).collect();
+        ShaderProgram::from_stages(stages, Some(shaders))
+    }
+
+    // Stages are only compiled when `shaders` is `None` and the program cache
+    // misses.
+    #[track_caller]
+    fn from_stages(
+        stages: Vec<ProgramStage>,
+        shaders: Option<&[Shader]>,
+    ) -> Result<ShaderProgram, RlibError> {
+        let sources: Vec<(u32, &str)> = stages
+            .iter()
+            .map(|stage| (stage.shader_type, stage.source.source.as_str()))
+            .collect();
+        let defines: Vec<&ShaderDefines> = stages
+            .iter()
+            .filter_map(|stage| stage.origin.as_ref().map(|origin| &origin.defines))
+            .collect();
+        let key = program_cache::get_key(&sources, &defines);
+
+        let handle = match key.and_then(program_cache::load) {
+            Some(handle) => handle,
+            None => {
+                let handle = match shaders {
+                    Some(shaders) => ShaderProgram::link(shaders)?,
+                    None => ShaderProgram::link(
+                        &stages
+                            .iter()
+                            .map(ProgramStage::compile)
+                            .collect::<Result<Vec<Shader>, RlibError>>()?,
+                    )?,
+                };
+
+                if let Some(key) = key {
+                    program_cache::store(key, handle);
+                }
+
+                handle
+            }
+        };
+
+        Ok(ShaderProgram::from_linked(handle, stages))
+    }
+
+    fn link(shaders: &[Shader]) -> Result<u32, RlibError> {
         let handle = unsafe { gl::CreateProgram() };
 
+        if handle == 0 {
+            return Err(RlibError::GlResource("shader program".to_string()));
+        }
+
+        if program_cache::is_enabled() {
+            unsafe {
+                gl::ProgramParameteri(handle, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+            }
+        }
+
         for shader in shaders {
             unsafe {
                 gl::AttachShader(handle, shader.get_handle());
@@ -135,13 +435,13 @@ impl ShaderProgram {
                 );
             }
 
-            error!("Error linking shader program: {}", error.to_string_lossy());
-
             unsafe {
                 gl::DeleteProgram(handle);
             }
 
-            return None;
+            return Err(RlibError::ProgramLink {
+                log: error.to_string_lossy().into_owned(),
+            });
         }
 
         for shader in shaders {
@@ -150,7 +450,14 @@ impl ShaderProgram {
             }
         }
 
-        let mut uniforms: HashMap<String, u32> = HashMap::new();
+        Ok(handle)
+    }
+
+    // Wraps a linked program, however it was linked.
+    #[track_caller]
+    fn from_linked(handle: u32, stages: Vec<ProgramStage>) -> ShaderProgram {
+        let mut uniforms: HashMap<String, UniformInfo> = HashMap::new();
+        let mut element_locations: HashMap<String, Vec<i32>> = HashMap::new();
         let mut uniform_count: i32 = 0;
         unsafe {
             gl::GetProgramiv(handle, gl::ACTIVE_UNIFORMS, &mut uniform_count);
@@ -177,37 +484,450 @@ impl ShaderProgram {
 
                 let var_loc = gl::GetUniformLocation(handle, var_name.as_ptr());
 
+                // Members of uniform blocks have no location of their own.
+                if var_loc < 0 {
+                    continue;
+                }
+
                 {
                     let mut string = var_name.to_string_lossy().into_owned();
                     string.truncate(name_len as usize);
 
-                    uniforms.insert(string, var_loc as u32);
+                    if let Some(array_name) = string.strip_suffix("[0]") {
+                        string = array_name.to_string();
+                    }
+
+                    if var_size > 1 {
+                        element_locations.insert(
+                            string.clone(),
+                            get_element_locations(handle, &string, var_size),
+                        );
+                    }
+
+                    uniforms.insert(
+                        string,
+                        UniformInfo {
+                            location: var_loc,
+                            gl_type: var_type,
+                            size: var_size,
+                        },
+                    );
                 }
             }
         }
 
-        Some(ShaderProgram {
-            handle: Rc::new(NativeShaderProgram { handle, uniforms }),
-        })
+        let mut blocks = uniform_buffer::reflect_blocks(handle, BlockKind::Uniform);
+        blocks.extend(uniform_buffer::reflect_blocks(
+            handle,
+            BlockKind::ShaderStorage,
+        ));
+
+        resource_tracker::register(
+            ResourceKind::ShaderProgram,
+            handle,
+            None,
+            0,
+            0,
+            std::panic::Location::caller(),
+        );
+
+        let program = ShaderProgram {
+            handle: Rc::new(NativeShaderProgram {
+                handle: Cell::new(handle),
+                uniforms: RefCell::new(uniforms),
+                element_locations: RefCell::new(element_locations),
+                blocks: RefCell::new(blocks),
+                reported: RefCell::new(HashSet::new()),
+                stages: RefCell::new(stages),
+            }),
+        };
+
+        if let Some(camera) = program.get_block(CAMERA_BLOCK_NAME) {
+            if cfg!(debug_assertions)
+                && camera.data_size as usize > std::mem::size_of::<CameraBlock>()
+            {
+                warn!(
+                    "Camera block of shader program {} is {} bytes, larger than the {} provided",
+                    handle,
+                    camera.data_size,
+                    std::mem::size_of::<CameraBlock>()
+                );
+            }
+
+            program.set_block_binding(CAMERA_BLOCK_NAME, CAMERA_BLOCK_BINDING);
+        }
+
+        program
+    }
+
+    // Loads a single file made of `#pragma stage` sections; see
+    // `shader_preprocessor::split_stages`.
+    #[track_caller]
+    pub fn from_file(
+        root: &Path,
+        path: &str,
+        defines: &ShaderDefines,
+    ) -> Result<ShaderProgram, RlibError> {
+        let source = shader_preprocessor::read_source(&root.join(path))?;
+        let mut stages = Vec::new();
+
+        for (stage, _) in shader_preprocessor::split_stages(path, &source)? {
+            let origin = ShaderOrigin {
+                root: root.to_path_buf(),
+                path: path.to_string(),
+                defines: defines.clone(),
+                combined: true,
+            };
+            stages.push(ProgramStage::from_origin(stage, origin)?);
+        }
+
+        let program = ShaderProgram::from_stages(stages, None)?;
+        program.set_label(path);
+
+        Ok(program)
+    }
+
+    // One file per stage, as with `Shader::from_file`. Unlike building the
+    // shaders first, nothing is compiled when the program cache has a binary.
+    #[track_caller]
+    pub fn from_stage_files(
+        root: &Path,
+        stages: &[(u32, &str)],
+        defines: &ShaderDefines,
+    ) -> Result<ShaderProgram, RlibError> {
+        let stages = stages
+            .iter()
+            .map(|(shader_type, path)| {
+                let origin = ShaderOrigin {
+                    root: root.to_path_buf(),
+                    path: path.to_string(),
+                    defines: defines.clone(),
+                    combined: false,
+                };
+                ProgramStage::from_origin(*shader_type, origin)
+            })
+            .collect::<Result<Vec<ProgramStage>, RlibError>>()?;
+
+        ShaderProgram::from_stages(stages, None)
+    }
+
+    #[track_caller]
+    pub fn from_source(
+        name: &str,
+        source: &str,
+        defines: &ShaderDefines,
+    ) -> Result<ShaderProgram, RlibError> {
+        let mut stages = Vec::new();
+
+        for (stage, stage_source) in shader_preprocessor::split_stages(name, source)? {
+            stages.push(ProgramStage {
+                shader_type: stage,
+                source: shader_preprocessor::preprocess(
+                    Path::new(""),
+                    name,
+                    &stage_source,
+                    defines,
+                )?,
+                origin: None,
+            });
+        }
+
+        let program = ShaderProgram::from_stages(stages, None)?;
+        program.set_label(name);
+
+        Ok(program)
+    }
+
+    // Rebuilds from the files behind every clone of this program; on failure
+    // the old program stays in use. Block bindings and the values of uniforms
+    // whose name and type didn't change carry over.
+    #[track_caller]
+    pub fn reload(&self) -> Result<(), RlibError> {
+        let stages = self
+            .handle
+            .stages
+            .borrow()
+            .iter()
+            .map(ProgramStage::reread)
+            .collect::<Result<Vec<ProgramStage>, RlibError>>()?;
+
+        let fresh = ShaderProgram::from_stages(stages, None)?;
+
+        for block in self.get_blocks() {
+            if let Some(fresh_block) = fresh.get_block(&block.name) {
+                if fresh_block.binding != block.binding {
+                    fresh.set_block_binding(&block.name, block.binding);
+                }
+            }
+        }
+
+        self.copy_uniforms_to(&fresh);
+
+        let (old, new) = (&self.handle, &fresh.handle);
+        old.handle.swap(&new.handle);
+        old.uniforms.swap(&new.uniforms);
+        old.element_locations.swap(&new.element_locations);
+        old.blocks.swap(&new.blocks);
+        old.stages.swap(&new.stages);
+        old.reported.borrow_mut().clear();
+
+        // `fresh` now owns the old GL program and deletes it when dropped.
+        let label = resource_tracker::swap_identity(
+            ResourceKind::ShaderProgram,
+            new.handle.get(),
+            old.handle.get(),
+        );
+        if let Some(label) = label {
+            debug::label_object(gl::PROGRAM, old.handle.get(), &label);
+        }
+
+        Ok(())
+    }
+
+    fn copy_uniforms_to(&self, target: &ShaderProgram) {
+        let (from, to) = (self.get_handle(), target.get_handle());
+        let target_uniforms = target.get_uniforms();
+
+        for (name, uniform) in self.get_uniforms().iter() {
+            let target_uniform = match target_uniforms.get(name) {
+                Some(target_uniform) if target_uniform.gl_type == uniform.gl_type => target_uniform,
+                _ => continue,
+            };
+
+            if uniform.size <= 1 || target_uniform.size <= 1 {
+                uniform::copy_uniform(
+                    uniform.gl_type,
+                    from,
+                    uniform.location,
+                    to,
+                    target_uniform.location,
+                );
+                continue;
+            }
+
+            let from_elements = self.handle.element_locations.borrow();
+            let to_elements = target.handle.element_locations.borrow();
+
+            if let (Some(from_elements), Some(to_elements)) =
+                (from_elements.get(name), to_elements.get(name))
+            {
+                for (from_location, to_location) in from_elements.iter().zip(to_elements.iter()) {
+                    if *from_location >= 0 && *to_location >= 0 {
+                        uniform::copy_uniform(
+                            uniform.gl_type,
+                            from,
+                            *from_location,
+                            to,
+                            *to_location,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Every file on disk this program was built from.
+    pub fn get_source_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Vec::new();
+
+        for stage in self.handle.stages.borrow().iter() {
+            for file in stage.source.paths.iter() {
+                if !files.contains(file) {
+                    files.push(file.clone());
+                }
+            }
+        }
+
+        files
+    }
+
+    pub fn get_stages(&self) -> Vec<ShaderStage> {
+        self.handle
+            .stages
+            .borrow()
+            .iter()
+            .filter_map(|stage| ShaderStage::from_gl(stage.shader_type))
+            .collect()
+    }
+
+    pub fn set_label(&self, label: &str) {
+        debug::label_object(gl::PROGRAM, self.get_handle(), label);
+        resource_tracker::set_label(ResourceKind::ShaderProgram, self.get_handle(), label);
+    }
+
+    pub fn get_handle(&self) -> u32 {
+        self.handle.handle.get()
     }
 
     pub fn set_float(&self, name: &str, value: f32) {
-        if let Some(uniform) = self.handle.uniforms.get(name) {
-            unsafe {
-                gl::Uniform1f(*uniform as i32, value);
+        self.set_uniform(name, &value);
+    }
+
+    pub fn set_sampler(&self, name: &str, unit: u32) {
+        self.set_uniform(name, &TextureUnit(unit));
+    }
+
+    // Unknown names and values that don't fit the uniform's type are reported
+    // in debug builds and otherwise left to GL to ignore.
+    pub fn set_uniform<V: UniformValue + ?Sized>(&self, name: &str, value: &V) {
+        let uniform = match self.get_uniform(name) {
+            Some(uniform) => uniform,
+            None => {
+                if cfg!(debug_assertions) {
+                    self.report(
+                        name,
+                        format!(
+                            "Shader program {} has no active uniform \"{}\"",
+                            self.get_handle(),
+                            name
+                        ),
+                    );
+                }
+                return;
+            }
+        };
+
+        if cfg!(debug_assertions) {
+            if !value.is_compatible(uniform.gl_type) {
+                self.report(
+                    name,
+                    format!(
+                        "Can't set {} uniform \"{}\" of shader program {} from a {}",
+                        uniform::uniform_type_name(uniform.gl_type),
+                        name,
+                        self.get_handle(),
+                        std::any::type_name::<V>()
+                    ),
+                );
+                return;
+            }
+
+            if value.get_count() > uniform.size as usize {
+                self.report(
+                    name,
+                    format!(
+                        "Setting {} elements of uniform \"{}\" of shader program {}, which only has {}",
+                        value.get_count(),
+                        name,
+                        self.get_handle(),
+                        uniform.size
+                    ),
+                );
             }
         }
+
+        value.upload(self.get_handle(), uniform.location);
     }
 
-    pub fn bind(&self) {
-        unsafe {
-            gl::UseProgram(self.handle.handle);
+    fn report(&self, name: &str, message: String) {
+        if self.handle.reported.borrow_mut().insert(name.to_string()) {
+            warn!("{}", message);
         }
     }
 
-    pub fn unbind(&self) {
-        unsafe {
-            gl::UseProgram(0);
+    // Also resolves single elements of arrays, such as `lights[2]`.
+    pub fn get_uniform(&self, name: &str) -> Option<UniformInfo> {
+        let uniforms = self.handle.uniforms.borrow();
+
+        if let Some(uniform) = uniforms.get(name) {
+            return Some(*uniform);
+        }
+
+        let (array_name, index) = name.strip_suffix(']')?.rsplit_once('[')?;
+        let index: i32 = index.parse().ok()?;
+        let uniform = uniforms.get(array_name)?;
+
+        if index < 0 || index >= uniform.size {
+            return None;
+        }
+
+        let location = if index == 0 {
+            uniform.location
+        } else {
+            *self
+                .handle
+                .element_locations
+                .borrow()
+                .get(array_name)?
+                .get(index as usize)?
+        };
+
+        if location < 0 {
+            return None;
         }
+
+        Some(UniformInfo {
+            location,
+            size: uniform.size - index,
+            ..*uniform
+        })
+    }
+
+    pub fn get_uniforms(&self) -> Ref<'_, HashMap<String, UniformInfo>> {
+        self.handle.uniforms.borrow()
     }
+
+    pub fn set_block_binding(&self, name: &str, binding: u32) {
+        let mut blocks = self.handle.blocks.borrow_mut();
+
+        match blocks.get_mut(name) {
+            Some(block) => {
+                unsafe {
+                    match block.kind {
+                        BlockKind::Uniform => {
+                            gl::UniformBlockBinding(self.get_handle(), block.index, binding)
+                        }
+                        BlockKind::ShaderStorage => {
+                            gl::ShaderStorageBlockBinding(self.get_handle(), block.index, binding)
+                        }
+                    }
+                }
+
+                block.binding = binding;
+            }
+            None => {
+                if cfg!(debug_assertions) {
+                    self.report(
+                        name,
+                        format!(
+                            "Shader program {} has no active block \"{}\"",
+                            self.get_handle(),
+                            name
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn get_block(&self, name: &str) -> Option<BlockInfo> {
+        self.handle.blocks.borrow().get(name).cloned()
+    }
+
+    pub fn get_blocks(&self) -> Vec<BlockInfo> {
+        self.handle.blocks.borrow().values().cloned().collect()
+    }
+
+    pub fn set_uniforms<U: Uniforms>(&self, uniforms: &U) {
+        uniforms.apply(self);
+    }
+
+    pub fn bind(&self) {
+        state_cache::use_program(self.get_handle());
+    }
+
+    pub fn unbind(&self) {
+        state_cache::use_program(0);
+    }
+}
+
+fn get_element_locations(program: u32, array_name: &str, size: i32) -> Vec<i32> {
+    (0..size)
+        .map(
+            |index| match CString::new(format!("{}[{}]", array_name, index)) {
+                Ok(element) => unsafe { gl::GetUniformLocation(program, element.as_ptr()) },
+                Err(_) => -1,
+            },
+        )
+        .collect()
 }
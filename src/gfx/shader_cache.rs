@@ -0,0 +1,144 @@
+use super::shader::ShaderProgram;
+use super::shader_preprocessor::ShaderDefines;
+use crate::error::RlibError;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum ProgramFiles {
+    Stages(Vec<(u32, String)>),
+    // A single file with `#pragma stage` sections.
+    Combined(String),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ProgramKey {
+    files: ProgramFiles,
+    defines: ShaderDefines,
+}
+
+// Programs built from shader files, one per combination of stage files and
+// define set, so every variant is only compiled once.
+pub struct ShaderCache {
+    root: PathBuf,
+    programs: HashMap<ProgramKey, ShaderProgram>,
+    // Inserted since the last `take_new_programs`, so hot reload can watch
+    // each program once instead of scanning the whole cache.
+    new_programs: Vec<ShaderProgram>,
+}
+
+impl ShaderCache {
+    pub fn new<P: AsRef<Path>>(root: P) -> ShaderCache {
+        ShaderCache {
+            root: root.as_ref().to_path_buf(),
+            programs: HashMap::new(),
+            new_programs: Vec::new(),
+        }
+    }
+
+    // `stages` pairs a shader type such as `gl::VERTEX_SHADER` with a path
+    // relative to the root.
+    #[track_caller]
+    pub fn load(
+        &mut self,
+        stages: &[(u32, &str)],
+        defines: &ShaderDefines,
+    ) -> Result<ShaderProgram, RlibError> {
+        let key = ProgramKey {
+            files: ProgramFiles::Stages(
+                stages
+                    .iter()
+                    .map(|(shader_type, path)| (*shader_type, path.to_string()))
+                    .collect(),
+            ),
+            defines: defines.clone(),
+        };
+
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(program.clone());
+        }
+
+        let program = ShaderProgram::from_stage_files(&self.root, stages, defines)?;
+        program.set_label(&describe(&key));
+
+        self.programs.insert(key, program.clone());
+        self.new_programs.push(program.clone());
+
+        Ok(program)
+    }
+
+    #[track_caller]
+    pub fn load_file(
+        &mut self,
+        path: &str,
+        defines: &ShaderDefines,
+    ) -> Result<ShaderProgram, RlibError> {
+        let key = ProgramKey {
+            files: ProgramFiles::Combined(path.to_string()),
+            defines: defines.clone(),
+        };
+
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(program.clone());
+        }
+
+        let program = ShaderProgram::from_file(&self.root, path, defines)?;
+        program.set_label(&describe(&key));
+
+        self.programs.insert(key, program.clone());
+        self.new_programs.push(program.clone());
+
+        Ok(program)
+    }
+
+    // Programs already handed out stay alive until their last clone drops.
+    pub fn clear(&mut self) {
+        self.programs.clear();
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get_programs(&self) -> impl Iterator<Item = &ShaderProgram> {
+        self.programs.values()
+    }
+
+    pub(crate) fn take_new_programs(&mut self) -> Vec<ShaderProgram> {
+        std::mem::take(&mut self.new_programs)
+    }
+
+    pub fn get_program_count(&self) -> usize {
+        self.programs.len()
+    }
+}
+
+fn describe(key: &ProgramKey) -> String {
+    let mut label = match &key.files {
+        ProgramFiles::Stages(stages) => stages
+            .iter()
+            .map(|(_, path)| path.as_str())
+            .collect::<Vec<&str>>()
+            .join(" + "),
+        ProgramFiles::Combined(path) => path.clone(),
+    };
+
+    if !key.defines.is_empty() {
+        let defines = key
+            .defines
+            .iter()
+            .map(|(name, value)| {
+                if value.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}={}", name, value)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        label = format!("{} [{}]", label, defines);
+    }
+
+    label
+}
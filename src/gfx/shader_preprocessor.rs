@@ -0,0 +1,533 @@
+use super::uniform_buffer::CAMERA_BLOCK_GLSL;
+use crate::error::RlibError;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+// Includes that resolve without touching the disk.
+const BUILTIN_INCLUDES: &[(&str, &str)] = &[("rlib/camera.glsl", CAMERA_BLOCK_GLSL)];
+
+// Ordered, so equal sets compare and hash the same regardless of insertion
+// order.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> ShaderDefines {
+        ShaderDefines::default()
+    }
+
+    pub fn with(mut self, name: &str, value: &str) -> ShaderDefines {
+        self.set(name, value);
+        self
+    }
+
+    pub fn with_flag(self, name: &str) -> ShaderDefines {
+        self.with(name, "")
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.defines.remove(name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.defines.get(name).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.defines
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defines.is_empty()
+    }
+}
+
+// `files[n]` is the file behind source string number `n` in the `#line`
+// directives of `source`. `paths` only lists the ones read from disk.
+#[derive(Clone, Debug)]
+pub struct PreprocessedShader {
+    pub source: String,
+    pub files: Vec<String>,
+    pub paths: Vec<PathBuf>,
+}
+
+impl PreprocessedShader {
+    // Rewrites `0:12(5): error` (Mesa), `0(12) : error` (NVIDIA) and
+    // `ERROR: 0:12: ...` (AMD, Intel) style locations to file names.
+    pub fn map_log(&self, log: &str) -> String {
+        let mut mapped = String::new();
+
+        for line in log.lines() {
+            let _ = writeln!(mapped, "{}", self.map_log_line(line));
+        }
+
+        mapped
+    }
+
+    fn map_log_line(&self, line: &str) -> String {
+        let prefix_len = ["ERROR: ", "WARNING: "]
+            .iter()
+            .find(|prefix| line.starts_with(*prefix))
+            .map_or(0, |prefix| prefix.len());
+        let (prefix, rest) = line.split_at(prefix_len);
+
+        let id_len = rest.bytes().take_while(u8::is_ascii_digit).count();
+        let file = match rest[..id_len].parse::<usize>() {
+            Ok(id) if id < self.files.len() => &self.files[id],
+            _ => return line.to_string(),
+        };
+
+        let rest = &rest[id_len..];
+        let parenthesized = rest.starts_with('(');
+        if !parenthesized && !rest.starts_with(':') {
+            return line.to_string();
+        }
+
+        let rest = &rest[1..];
+        let line_len = rest.bytes().take_while(u8::is_ascii_digit).count();
+        if line_len == 0 {
+            return line.to_string();
+        }
+
+        let (line_number, mut rest) = rest.split_at(line_len);
+        if parenthesized {
+            match rest.strip_prefix(')') {
+                Some(after) => rest = after,
+                None => return line.to_string(),
+            }
+        }
+
+        format!("{}{}:{}{}", prefix, file, line_number, rest)
+    }
+}
+
+// Splits a file with `#pragma stage <name>` sections into one source per
+// stage. Everything before the first section, such as `#version`, is shared
+// by all of them. Lines belonging to other stages are blanked rather than
+// removed so line numbers still match the file.
+pub fn split_stages(name: &str, source: &str) -> Result<Vec<(u32, String)>, RlibError> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut sections: Vec<(u32, usize)> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let mut words = line.split_whitespace();
+
+        if words.next() != Some("#pragma") || words.next() != Some("stage") {
+            continue;
+        }
+
+        let stage_name = words.next().unwrap_or("");
+        let stage = parse_stage(stage_name).ok_or_else(|| {
+            RlibError::Parse(format!(
+                "{}:{}: unknown shader stage \"{}\"",
+                name,
+                index + 1,
+                stage_name
+            ))
+        })?;
+
+        if sections.iter().any(|(existing, _)| *existing == stage) {
+            return Err(RlibError::Parse(format!(
+                "{}:{}: duplicate \"{}\" stage",
+                name,
+                index + 1,
+                stage_name
+            )));
+        }
+
+        sections.push((stage, index));
+    }
+
+    let shared_end = match sections.first() {
+        Some((_, start)) => *start,
+        None => {
+            return Err(RlibError::Parse(format!(
+                "{}: no #pragma stage sections",
+                name
+            )))
+        }
+    };
+
+    let stages = sections
+        .iter()
+        .enumerate()
+        .map(|(section, (stage, start))| {
+            let end = sections
+                .get(section + 1)
+                .map_or(lines.len(), |(_, next)| *next);
+            let mut stage_source = String::new();
+
+            for (index, line) in lines.iter().enumerate() {
+                if index < shared_end || (index > *start && index < end) {
+                    stage_source.push_str(line);
+                }
+                stage_source.push('\n');
+            }
+
+            (*stage, stage_source)
+        })
+        .collect();
+
+    Ok(stages)
+}
+
+fn parse_stage(name: &str) -> Option<u32> {
+    match name {
+        "vertex" => Some(gl::VERTEX_SHADER),
+        "fragment" => Some(gl::FRAGMENT_SHADER),
+        "geometry" => Some(gl::GEOMETRY_SHADER),
+        "tess_control" => Some(gl::TESS_CONTROL_SHADER),
+        "tess_evaluation" => Some(gl::TESS_EVALUATION_SHADER),
+        "compute" => Some(gl::COMPUTE_SHADER),
+        _ => None,
+    }
+}
+
+pub fn preprocess_file(
+    root: &Path,
+    path: &str,
+    defines: &ShaderDefines,
+) -> Result<PreprocessedShader, RlibError> {
+    let full_path = root.join(path);
+    let source = read_source(&full_path)?;
+
+    let mut shader = preprocess(root, path, &source, defines)?;
+    shader.paths.insert(0, full_path);
+
+    Ok(shader)
+}
+
+// Like a failed `#include`, the error names the file.
+pub(crate) fn read_source(path: &Path) -> Result<String, RlibError> {
+    std::fs::read_to_string(path).map_err(|source| RlibError::Io {
+        path: Some(path.to_path_buf()),
+        source,
+    })
+}
+
+// Includes resolve against the builtins first and then `root`. Each file is
+// only included once per shader, and defines go right after `#version`.
+pub fn preprocess(
+    root: &Path,
+    name: &str,
+    source: &str,
+    defines: &ShaderDefines,
+) -> Result<PreprocessedShader, RlibError> {
+    let mut preprocessor = Preprocessor {
+        root,
+        defines,
+        files: Vec::new(),
+        paths: Vec::new(),
+        included: HashSet::new(),
+        stack: Vec::new(),
+        output: String::new(),
+    };
+
+    preprocessor.process(name, source, true)?;
+
+    Ok(PreprocessedShader {
+        source: preprocessor.output,
+        files: preprocessor.files,
+        paths: preprocessor.paths,
+    })
+}
+
+struct Preprocessor<'a> {
+    root: &'a Path,
+    defines: &'a ShaderDefines,
+    files: Vec<String>,
+    paths: Vec<PathBuf>,
+    included: HashSet<String>,
+    stack: Vec<String>,
+    output: String,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn process(&mut self, name: &str, source: &str, is_root: bool) -> Result<(), RlibError> {
+        let id = self.files.len();
+        self.files.push(name.to_string());
+        self.included.insert(name.to_string());
+        self.stack.push(name.to_string());
+
+        let has_version = source
+            .lines()
+            .any(|line| line.trim_start().starts_with("#version"));
+
+        if is_root && !has_version {
+            self.write_defines();
+            let _ = writeln!(self.output, "#line 1 {}", id);
+        }
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim_start();
+
+            if trimmed.starts_with("#version") {
+                if is_root {
+                    let _ = writeln!(self.output, "{}", line);
+                    self.write_defines();
+                    let _ = writeln!(self.output, "#line {} {}", line_number + 1, id);
+                } else {
+                    self.output.push('\n');
+                }
+            } else if let Some(directive) = trimmed.strip_prefix("#include") {
+                let include = parse_include_name(directive).ok_or_else(|| {
+                    RlibError::Parse(format!(
+                        "{}:{}: malformed #include, expected a quoted file name",
+                        name, line_number
+                    ))
+                })?;
+
+                self.include(name, line_number, include)?;
+                let _ = writeln!(self.output, "#line {} {}", line_number + 1, id);
+            } else {
+                let _ = writeln!(self.output, "{}", line);
+            }
+        }
+
+        self.stack.pop();
+
+        Ok(())
+    }
+
+    fn include(&mut self, from: &str, line_number: usize, name: &str) -> Result<(), RlibError> {
+        if self.stack.iter().any(|file| file == name) {
+            return Err(RlibError::Parse(format!(
+                "{}:{}: recursive #include of \"{}\"",
+                from, line_number, name
+            )));
+        }
+
+        if self.included.contains(name) {
+            return Ok(());
+        }
+
+        let source = match BUILTIN_INCLUDES
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+        {
+            Some((_, source)) => source.to_string(),
+            None => {
+                let path = self.root.join(name);
+                let source = read_source(&path)?;
+
+                self.paths.push(path);
+                source
+            }
+        };
+
+        let id = self.files.len();
+        let _ = writeln!(self.output, "#line 1 {}", id);
+
+        self.process(name, &source, false)
+    }
+
+    fn write_defines(&mut self) {
+        for (name, value) in self.defines.iter() {
+            let _ = writeln!(self.output, "#define {} {}", name, value);
+        }
+    }
+}
+
+fn parse_include_name(directive: &str) -> Option<&str> {
+    let directive = directive.trim();
+
+    directive
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .or_else(|| {
+            directive
+                .strip_prefix('<')
+                .and_then(|rest| rest.strip_suffix('>'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn shader_with_files(files: &[&str]) -> PreprocessedShader {
+        PreprocessedShader {
+            source: String::new(),
+            files: files.iter().map(|file| file.to_string()).collect(),
+            paths: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn maps_mesa_log_locations() {
+        let shader = shader_with_files(&["main.glsl", "lib.glsl"]);
+        assert_eq!(
+            shader.map_log("1:12(5): error: `x' undeclared\n"),
+            "lib.glsl:12(5): error: `x' undeclared\n"
+        );
+    }
+
+    #[test]
+    fn maps_nvidia_log_locations() {
+        let shader = shader_with_files(&["main.glsl", "lib.glsl"]);
+        assert_eq!(
+            shader.map_log("0(7) : error C1008: undefined variable \"x\""),
+            "main.glsl:7 : error C1008: undefined variable \"x\"\n"
+        );
+    }
+
+    #[test]
+    fn maps_amd_and_intel_log_locations() {
+        let shader = shader_with_files(&["main.glsl", "lib.glsl"]);
+        assert_eq!(
+            shader.map_log("ERROR: 1:3: 'x' : undeclared identifier\nWARNING: 0:9: unused"),
+            "ERROR: lib.glsl:3: 'x' : undeclared identifier\nWARNING: main.glsl:9: unused\n"
+        );
+    }
+
+    #[test]
+    fn leaves_unknown_log_lines_alone() {
+        let shader = shader_with_files(&["main.glsl"]);
+        assert_eq!(
+            shader.map_log("4:2(1): error: no such file\nlink failed\n0:x: odd"),
+            "4:2(1): error: no such file\nlink failed\n0:x: odd\n"
+        );
+    }
+
+    #[test]
+    fn splits_stages_keeping_line_numbers() {
+        let source = "#version 430 core\n\
+                      #pragma stage vertex\n\
+                      void main() { gl_Position = vec4(0); }\n\
+                      #pragma stage fragment\n\
+                      out vec4 color;\n\
+                      void main() { color = vec4(1); }\n";
+        let stages = split_stages("test.glsl", source).unwrap();
+
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].0, gl::VERTEX_SHADER);
+        assert_eq!(stages[1].0, gl::FRAGMENT_SHADER);
+
+        for (_, stage_source) in stages.iter() {
+            assert_eq!(stage_source.lines().count(), source.lines().count());
+            assert!(stage_source.starts_with("#version 430 core\n"));
+            assert!(!stage_source.contains("#pragma stage"));
+        }
+
+        let vertex: Vec<&str> = stages[0].1.lines().collect();
+        assert_eq!(vertex[2], "void main() { gl_Position = vec4(0); }");
+        assert_eq!(vertex[4], "");
+
+        let fragment: Vec<&str> = stages[1].1.lines().collect();
+        assert_eq!(fragment[2], "");
+        assert_eq!(fragment[4], "out vec4 color;");
+    }
+
+    #[test]
+    fn rejects_unknown_duplicate_and_missing_stages() {
+        let unknown = split_stages("a.glsl", "#version 430\n#pragma stage pixel\n");
+        match unknown {
+            Err(RlibError::Parse(message)) => {
+                assert_eq!(message, "a.glsl:2: unknown shader stage \"pixel\"")
+            }
+            _ => panic!("expected a parse error"),
+        }
+
+        let duplicate = split_stages(
+            "b.glsl",
+            "#pragma stage vertex\nvoid main() {}\n#pragma stage vertex\n",
+        );
+        match duplicate {
+            Err(RlibError::Parse(message)) => {
+                assert_eq!(message, "b.glsl:3: duplicate \"vertex\" stage")
+            }
+            _ => panic!("expected a parse error"),
+        }
+
+        assert!(split_stages("c.glsl", "void main() {}\n").is_err());
+    }
+
+    #[test]
+    fn writes_defines_and_line_directives() {
+        let defines = ShaderDefines::new().with("COUNT", "4").with_flag("SHADOWS");
+        let source = "#version 430 core\n#include \"rlib/camera.glsl\"\nvoid main() {}\n";
+        let shader = preprocess(Path::new(""), "main.glsl", source, &defines).unwrap();
+
+        let expected_prefix = "#version 430 core\n\
+                               #define COUNT 4\n\
+                               #define SHADOWS \n\
+                               #line 2 0\n\
+                               #line 1 1\n";
+        assert!(
+            shader.source.starts_with(expected_prefix),
+            "{}",
+            shader.source
+        );
+        assert!(shader.source.ends_with("#line 3 0\nvoid main() {}\n"));
+        assert!(shader.source.contains(CAMERA_BLOCK_GLSL.trim()));
+        assert_eq!(shader.files, ["main.glsl", "rlib/camera.glsl"]);
+        assert!(shader.paths.is_empty());
+    }
+
+    #[test]
+    fn includes_each_file_once() {
+        let dir = TempDir::new("preprocessor-once");
+        dir.write("common.glsl", "float common_value;\n");
+        dir.write("lib.glsl", "#include \"common.glsl\"\nfloat lib_value;\n");
+
+        let source = "#include \"common.glsl\"\n#include \"lib.glsl\"\n";
+        let shader =
+            preprocess(dir.get_path(), "main.glsl", source, &ShaderDefines::new()).unwrap();
+
+        assert_eq!(shader.source.matches("float common_value;").count(), 1);
+        assert_eq!(shader.files, ["main.glsl", "common.glsl", "lib.glsl"]);
+        assert_eq!(
+            shader.paths,
+            [dir.join("common.glsl"), dir.join("lib.glsl")]
+        );
+    }
+
+    #[test]
+    fn rejects_recursive_includes() {
+        let dir = TempDir::new("preprocessor-recursive");
+        dir.write("a.glsl", "#include \"b.glsl\"\n");
+        dir.write("b.glsl", "\n#include \"a.glsl\"\n");
+
+        match preprocess_file(dir.get_path(), "a.glsl", &ShaderDefines::new()) {
+            Err(RlibError::Parse(message)) => {
+                assert_eq!(message, "b.glsl:2: recursive #include of \"a.glsl\"")
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn missing_files_are_named() {
+        let dir = TempDir::new("preprocessor-missing");
+        dir.write("main.glsl", "#include \"nope.glsl\"\n");
+
+        match preprocess_file(dir.get_path(), "gone.glsl", &ShaderDefines::new()) {
+            Err(RlibError::Io {
+                path: Some(path),
+                source,
+            }) => {
+                assert_eq!(path, dir.join("gone.glsl"));
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            _ => panic!("expected an I/O error"),
+        }
+
+        match preprocess_file(dir.get_path(), "main.glsl", &ShaderDefines::new()) {
+            Err(RlibError::Io {
+                path: Some(path), ..
+            }) => assert_eq!(path, dir.join("nope.glsl")),
+            _ => panic!("expected an I/O error"),
+        }
+    }
+}
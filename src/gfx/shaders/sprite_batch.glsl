@@ -0,0 +1,33 @@
+#version 430 core
+
+#pragma stage vertex
+
+#include "rlib/camera.glsl"
+
+layout (location = 0) in vec2 in_position;
+layout (location = 1) in vec2 in_uv;
+layout (location = 2) in vec4 in_color;
+
+layout (location = 0) out vec4 out_color;
+layout (location = 1) out vec2 out_uv;
+
+void main() {
+    gl_Position = u_projection * u_view * vec4(in_position, 0.0, 1.0);
+
+    out_color = in_color;
+    out_uv = in_uv;
+}
+
+#pragma stage fragment
+
+layout (location = 0) in vec4 in_color;
+layout (location = 1) in vec2 in_uv;
+
+layout (location = 0) out vec4 out_color;
+
+uniform sampler2D u_texture;
+
+void main() {
+    vec4 color = texture(u_texture, in_uv) * in_color;
+    out_color = color;
+}
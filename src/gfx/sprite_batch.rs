@@ -1,10 +1,15 @@
-use super::buffer::Buffer;
+use super::buffer::{Buffer, BufferTarget, BufferUsage, IndexType};
 use super::color::colors;
 use super::color::Color;
-use super::shader::Shader;
+use super::debug::DebugGroup;
 use super::shader::ShaderProgram;
+use super::shader_preprocessor::ShaderDefines;
+use super::stream_buffer::StreamBuffer;
 use super::texture::Texture;
 use super::texture_region::TextureRegion;
+use super::vertex_array::{Vertex, VertexArray};
+use crate::error::RlibError;
+use bytemuck::{Pod, Zeroable};
 use glam::{Vec2, Vec4};
 
 #[derive(Clone)]
@@ -32,21 +37,19 @@ impl Sprite {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Pod, Zeroable, Vertex)]
 #[repr(C)]
 struct SpriteVertex {
-    pub x: f32,
-    pub y: f32,
-    pub u: f32,
-    pub v: f32,
-    pub color: f32,
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    #[vertex(normalized)]
+    pub color: [u8; 4],
 }
 
 pub struct SpriteBatch<const COUNT: usize> {
     vertices: [SpriteVertex; COUNT],
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
-    vao_handle: u32,
+    vertex_buffer: StreamBuffer<SpriteVertex>,
+    vertex_array: VertexArray,
     vertex_offset: u32,
     last_texture: Option<Texture>,
     drawing: bool,
@@ -54,106 +57,9 @@ pub struct SpriteBatch<const COUNT: usize> {
 }
 
 impl<const COUNT: usize> SpriteBatch<COUNT> {
-    pub fn new() -> Option<SpriteBatch<COUNT>> {
-        let vertex_shader = Shader::from_string(
-            gl::VERTEX_SHADER,
-            "
-            #version 430 core
-            
-            layout (location = 0) in vec2 in_position;
-            layout (location = 1) in vec2 in_uv;
-            layout (location = 2) in vec4 in_color;
-
-            layout (location = 0) out vec4 out_color;
-            layout (location = 1) out vec2 out_uv;
-
-            void main() {
-                gl_Position = vec4(in_position, 0.0, 1.0);
-
-                out_color = in_color;
-                out_uv = in_uv;
-            }
-        ",
-        )
-        .unwrap();
-
-        let fragment_shader = Shader::from_string(
-            gl::FRAGMENT_SHADER,
-            "
-            #version 430 core
-
-            layout (location = 0) in vec4 in_color;
-            layout (location = 1) in vec2 in_uv;
-
-            layout (location = 0) out vec4 out_color;
-
-            uniform sampler2D u_texture;
-
-            void main() {
-                vec4 color = texture(u_texture, in_uv) * in_color;
-                out_color = color;
-            }
-        ",
-        )
-        .unwrap();
-
-        let vertex_buffer = Buffer::new_with_capacity(
-            gl::ARRAY_BUFFER,
-            gl::DYNAMIC_DRAW,
-            (COUNT * std::mem::size_of::<SpriteVertex>()) as u32,
-        )
-        .unwrap();
-        let mut index_buffer = Buffer::new_with_capacity(
-            gl::ELEMENT_ARRAY_BUFFER,
-            gl::STATIC_DRAW,
-            (COUNT * std::mem::size_of::<u32>() * 6) as u32,
-        )
-        .unwrap();
-
-        let mut vao_handle = 0;
-
-        unsafe {
-            gl::GenVertexArrays(1, &mut vao_handle);
-            gl::BindVertexArray(vao_handle);
-
-            vertex_buffer.bind();
-
-            let pos_offset = 0;
-            let uv_offset = 2 * std::mem::size_of::<f32>();
-            let color_offset = 4 * std::mem::size_of::<f32>();
-
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(
-                0,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                (5 * std::mem::size_of::<f32>()) as gl::types::GLint,
-                pos_offset as *const std::os::raw::c_void,
-            );
-
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(
-                1,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                (5 * std::mem::size_of::<f32>()) as gl::types::GLint,
-                uv_offset as *const std::os::raw::c_void,
-            );
-
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribPointer(
-                2,
-                4,
-                gl::UNSIGNED_BYTE,
-                gl::TRUE,
-                (5 * std::mem::size_of::<f32>()) as gl::types::GLint,
-                color_offset as *const std::os::raw::c_void,
-            );
-            gl::BindVertexArray(0);
-            vertex_buffer.unbind();
-        }
+    #[track_caller]
+    pub fn new() -> Result<SpriteBatch<COUNT>, RlibError> {
+        let vertex_buffer = StreamBuffer::new(BufferTarget::Vertex, COUNT)?;
 
         let mut indices: Vec<u32> = vec![0; COUNT * 6];
 
@@ -176,23 +82,32 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
             }
         }
 
-        index_buffer.set_data_u32(indices.as_slice());
+        let index_buffer =
+            Buffer::from_slice(BufferTarget::Index, BufferUsage::Static, indices.as_slice())?;
+
+        let shader_program = ShaderProgram::from_source(
+            "sprite_batch.glsl",
+            include_str!("shaders/sprite_batch.glsl"),
+            &ShaderDefines::new(),
+        )?;
+
+        let mut vertex_array = VertexArray::new()?;
+        vertex_array.add_vertex_buffer(vertex_buffer.get_buffer());
+        vertex_array.set_index_buffer(&index_buffer);
 
-        Some(SpriteBatch {
+        vertex_buffer.set_label("SpriteBatch vertices");
+        index_buffer.set_label("SpriteBatch indices");
+        shader_program.set_label("SpriteBatch");
+        vertex_array.set_label("SpriteBatch");
+
+        Ok(SpriteBatch {
             vertex_buffer,
-            index_buffer,
-            vao_handle,
-            vertices: [SpriteVertex {
-                x: 0.0,
-                y: 0.0,
-                u: 0.0,
-                v: 0.0,
-                color: 0.0,
-            }; COUNT],
+            vertex_array,
+            vertices: [SpriteVertex::zeroed(); COUNT],
             vertex_offset: 0,
             last_texture: None,
             drawing: false,
-            shader_program: ShaderProgram::from_shaders(&[fragment_shader, vertex_shader]).unwrap(),
+            shader_program,
         })
     }
 
@@ -220,15 +135,13 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
             None => colors::WHITE,
         };
 
-        let c = color.to_rgba8();
+        let c: [u8; 4] = bytemuck::cast(color.to_rgba8());
 
         {
             let vtx: &mut SpriteVertex = &mut self.vertices[self.vertex_offset as usize];
 
-            vtx.x = x;
-            vtx.y = y;
-            vtx.u = 0.0;
-            vtx.v = 1.0;
+            vtx.position = [x, y];
+            vtx.uv = [0.0, 1.0];
             vtx.color = c;
 
             self.vertex_offset += 1;
@@ -236,10 +149,8 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
         {
             let vtx: &mut SpriteVertex = &mut self.vertices[self.vertex_offset as usize];
 
-            vtx.x = x + width;
-            vtx.y = y;
-            vtx.u = 1.0;
-            vtx.v = 1.0;
+            vtx.position = [x + width, y];
+            vtx.uv = [1.0, 1.0];
             vtx.color = c;
 
             self.vertex_offset += 1;
@@ -247,10 +158,8 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
         {
             let vtx: &mut SpriteVertex = &mut self.vertices[self.vertex_offset as usize];
 
-            vtx.x = x + width;
-            vtx.y = y + height;
-            vtx.u = 1.0;
-            vtx.v = 0.0;
+            vtx.position = [x + width, y + height];
+            vtx.uv = [1.0, 0.0];
             vtx.color = c;
 
             self.vertex_offset += 1;
@@ -258,10 +167,8 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
         {
             let vtx: &mut SpriteVertex = &mut self.vertices[self.vertex_offset as usize];
 
-            vtx.x = x;
-            vtx.y = y + height;
-            vtx.u = 0.0;
-            vtx.v = 0.0;
+            vtx.position = [x, y + height];
+            vtx.uv = [0.0, 0.0];
             vtx.color = c;
 
             self.vertex_offset += 1;
@@ -283,7 +190,7 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
     pub fn draw_sprite(&mut self, sprite: &Sprite) {
         self.check_state(sprite.region.get_texture());
 
-        let c = sprite.color.to_rgba8();
+        let c: [u8; 4] = bytemuck::cast(sprite.color.to_rgba8());
 
         let origin = Vec2::new(
             sprite.origin.x + sprite.position.x,
@@ -324,10 +231,8 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
         {
             let vtx: &mut SpriteVertex = &mut self.vertices[self.vertex_offset as usize];
 
-            vtx.x = p1.x;
-            vtx.y = p1.y;
-            vtx.u = sprite.region.get_u();
-            vtx.v = sprite.region.get_v();
+            vtx.position = [p1.x, p1.y];
+            vtx.uv = [sprite.region.get_u(), sprite.region.get_v()];
             vtx.color = c;
 
             self.vertex_offset += 1;
@@ -335,10 +240,8 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
         {
             let vtx: &mut SpriteVertex = &mut self.vertices[self.vertex_offset as usize];
 
-            vtx.x = p2.x;
-            vtx.y = p2.y;
-            vtx.u = sprite.region.get_u2();
-            vtx.v = sprite.region.get_v();
+            vtx.position = [p2.x, p2.y];
+            vtx.uv = [sprite.region.get_u2(), sprite.region.get_v()];
             vtx.color = c;
 
             self.vertex_offset += 1;
@@ -346,10 +249,8 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
         {
             let vtx: &mut SpriteVertex = &mut self.vertices[self.vertex_offset as usize];
 
-            vtx.x = p3.x;
-            vtx.y = p3.y;
-            vtx.u = sprite.region.get_u2();
-            vtx.v = sprite.region.get_v2();
+            vtx.position = [p3.x, p3.y];
+            vtx.uv = [sprite.region.get_u2(), sprite.region.get_v2()];
             vtx.color = c;
 
             self.vertex_offset += 1;
@@ -357,10 +258,8 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
         {
             let vtx: &mut SpriteVertex = &mut self.vertices[self.vertex_offset as usize];
 
-            vtx.x = p4.x;
-            vtx.y = p4.y;
-            vtx.u = sprite.region.get_u();
-            vtx.v = sprite.region.get_v2();
+            vtx.position = [p4.x, p4.y];
+            vtx.uv = [sprite.region.get_u(), sprite.region.get_v2()];
             vtx.color = c;
 
             self.vertex_offset += 1;
@@ -379,34 +278,26 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
 
     pub fn flush_batch(&mut self) {
         if self.vertex_offset > 0 {
-            unsafe {
-                let ptr = std::slice::from_raw_parts(
-                    (&self.vertices[0] as *const SpriteVertex) as *const f32,
-                    (self.vertex_offset as usize * std::mem::size_of::<SpriteVertex>()) as usize,
-                );
+            let _group = DebugGroup::new("SpriteBatch::flush_batch");
 
-                self.vertex_buffer.bind();
-                self.vertex_buffer.copy_data_part(
-                    ptr,
-                    (self.vertex_offset * (std::mem::size_of::<SpriteVertex>()) as u32) as isize,
-                    0,
-                );
+            let vertex_count = self.vertex_offset as usize;
+            let base_vertex = self.vertex_buffer.write(&self.vertices[..vertex_count]);
 
+            unsafe {
                 let offset = 0;
 
-                gl::BindVertexArray(self.vao_handle);
-                self.index_buffer.bind();
+                self.vertex_array.bind();
 
                 self.shader_program.bind();
-                self.shader_program.set_float("u_t", 100.0);
 
                 self.last_texture.as_ref().unwrap().bind(0);
 
-                gl::DrawElements(
+                gl::DrawElementsBaseVertex(
                     gl::TRIANGLES,
                     ((self.vertex_offset / 4) * 6) as i32,
-                    gl::UNSIGNED_INT,
+                    u32::GL_TYPE,
                     offset as *const std::os::raw::c_void,
+                    base_vertex as i32,
                 );
             }
         }
@@ -429,3 +320,43 @@ impl<const COUNT: usize> SpriteBatch<COUNT> {
         self.last_texture = Some(texture.clone());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::vertex_array::{AttributeType, VertexAttribute};
+
+    #[test]
+    fn sprite_vertex_layout_matches_the_struct() {
+        let layout = SpriteVertex::layout();
+
+        assert_eq!(layout.stride, 20);
+        assert_eq!(layout.divisor, 0);
+        assert_eq!(
+            layout.attributes,
+            [
+                VertexAttribute {
+                    location: 0,
+                    components: 2,
+                    attribute_type: AttributeType::F32,
+                    normalized: false,
+                    offset: 0,
+                },
+                VertexAttribute {
+                    location: 1,
+                    components: 2,
+                    attribute_type: AttributeType::F32,
+                    normalized: false,
+                    offset: 8,
+                },
+                VertexAttribute {
+                    location: 2,
+                    components: 4,
+                    attribute_type: AttributeType::U8,
+                    normalized: true,
+                    offset: 16,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use super::deletion_queue::GlObject;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StateCacheStats {
+    pub calls_issued: u64,
+    pub calls_skipped: u64,
+}
+
+// `None` means "unknown", which is what everything goes back to after raw GL
+// calls, so the next request always reaches the driver.
+#[derive(Default)]
+struct StateCache {
+    program: Option<u32>,
+    vertex_array: Option<u32>,
+    buffers: HashMap<u32, u32>,
+    indexed_buffers: HashMap<(u32, u32), u32>,
+    active_texture_unit: Option<u32>,
+    textures: HashMap<(u32, u32), u32>,
+    blend: Option<bool>,
+    blend_func: Option<(u32, u32)>,
+    depth_test: Option<bool>,
+    scissor_test: Option<bool>,
+    viewport: Option<(i32, i32, i32, i32)>,
+    stats: StateCacheStats,
+}
+
+thread_local! {
+    static STATE: RefCell<StateCache> = RefCell::new(StateCache::default());
+}
+
+fn update<T: PartialEq + Copy, S, F>(select: S, value: T, apply: F)
+where
+    S: FnOnce(&mut StateCache) -> &mut Option<T>,
+    F: FnOnce(),
+{
+    let changed = STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let slot = select(&mut state);
+        let changed = *slot != Some(value);
+        *slot = Some(value);
+
+        if changed {
+            state.stats.calls_issued += 1;
+        } else {
+            state.stats.calls_skipped += 1;
+        }
+
+        changed
+    });
+
+    if changed {
+        apply();
+    }
+}
+
+pub fn use_program(handle: u32) {
+    update(
+        |s| &mut s.program,
+        handle,
+        || unsafe {
+            gl::UseProgram(handle);
+        },
+    );
+}
+
+pub fn bind_vertex_array(handle: u32) {
+    let changed = STATE.with(|state| state.borrow().vertex_array != Some(handle));
+
+    // The element array binding belongs to the VAO, so it's unknown again
+    // once a different one is bound.
+    if changed {
+        STATE.with(|state| state.borrow_mut().buffers.remove(&gl::ELEMENT_ARRAY_BUFFER));
+    }
+
+    update(
+        |s| &mut s.vertex_array,
+        handle,
+        || unsafe {
+            gl::BindVertexArray(handle);
+        },
+    );
+}
+
+pub fn bind_buffer(target: u32, handle: u32) {
+    let changed = STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let changed = state.buffers.insert(target, handle) != Some(handle);
+
+        if changed {
+            state.stats.calls_issued += 1;
+        } else {
+            state.stats.calls_skipped += 1;
+        }
+
+        changed
+    });
+
+    if changed {
+        unsafe {
+            gl::BindBuffer(target, handle);
+        }
+    }
+}
+
+// Binding to an indexed target also replaces its generic binding.
+pub fn bind_buffer_base(target: u32, index: u32, handle: u32) {
+    let changed = STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let changed = state.indexed_buffers.insert((target, index), handle) != Some(handle);
+
+        if changed {
+            state.buffers.insert(target, handle);
+            state.stats.calls_issued += 1;
+        } else {
+            state.stats.calls_skipped += 1;
+        }
+
+        changed
+    });
+
+    if changed {
+        unsafe {
+            gl::BindBufferBase(target, index, handle);
+        }
+    }
+}
+
+pub fn bind_texture(unit: u32, target: u32, handle: u32) {
+    let changed = STATE.with(|state| {
+        let state = state.borrow();
+        state.textures.get(&(unit, target)) != Some(&handle)
+    });
+
+    if !changed {
+        STATE.with(|state| state.borrow_mut().stats.calls_skipped += 1);
+        return;
+    }
+
+    set_active_texture_unit(unit);
+
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.textures.insert((unit, target), handle);
+        state.stats.calls_issued += 1;
+    });
+
+    unsafe {
+        gl::BindTexture(target, handle);
+    }
+}
+
+pub fn set_active_texture_unit(unit: u32) {
+    update(
+        |s| &mut s.active_texture_unit,
+        unit,
+        || unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+        },
+    );
+}
+
+pub fn set_blend(enabled: bool) {
+    update(
+        |s| &mut s.blend,
+        enabled,
+        || set_capability(gl::BLEND, enabled),
+    );
+}
+
+pub fn set_blend_func(src: u32, dst: u32) {
+    update(
+        |s| &mut s.blend_func,
+        (src, dst),
+        || unsafe {
+            gl::BlendFunc(src, dst);
+        },
+    );
+}
+
+pub fn set_depth_test(enabled: bool) {
+    update(
+        |s| &mut s.depth_test,
+        enabled,
+        || set_capability(gl::DEPTH_TEST, enabled),
+    );
+}
+
+pub fn set_scissor_test(enabled: bool) {
+    update(
+        |s| &mut s.scissor_test,
+        enabled,
+        || set_capability(gl::SCISSOR_TEST, enabled),
+    );
+}
+
+pub fn set_viewport(x: i32, y: i32, width: i32, height: i32) {
+    update(
+        |s| &mut s.viewport,
+        (x, y, width, height),
+        || unsafe {
+            gl::Viewport(x, y, width, height);
+        },
+    );
+}
+
+fn set_capability(capability: u32, enabled: bool) {
+    unsafe {
+        if enabled {
+            gl::Enable(capability);
+        } else {
+            gl::Disable(capability);
+        }
+    }
+}
+
+// Call after issuing raw GL that may have changed any of the tracked state.
+pub fn invalidate() {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let stats = state.stats;
+        *state = StateCache {
+            stats,
+            ..Default::default()
+        };
+    });
+}
+
+// GL unbinds deleted objects and may hand the name out again, so a cached
+// binding of it would otherwise skip a bind that is needed.
+pub(crate) fn forget(object: GlObject) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        match object {
+            GlObject::Texture(handle) => state.textures.retain(|_, bound| *bound != handle),
+            GlObject::Buffer(handle) => {
+                state.buffers.retain(|_, bound| *bound != handle);
+                state.indexed_buffers.retain(|_, bound| *bound != handle);
+            }
+            GlObject::Program(handle) => {
+                if state.program == Some(handle) {
+                    state.program = None;
+                }
+            }
+            GlObject::VertexArray(handle) => {
+                if state.vertex_array == Some(handle) {
+                    state.vertex_array = None;
+                    state.buffers.remove(&gl::ELEMENT_ARRAY_BUFFER);
+                }
+            }
+            GlObject::Shader(_) | GlObject::Fence(_) => {}
+        }
+    });
+}
+
+// DSA edits to a VAO's element buffer change the element array binding
+// without going through `bind_buffer`.
+pub(crate) fn forget_element_array_buffer(vertex_array: u32) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        if state.vertex_array == Some(vertex_array) {
+            state.buffers.remove(&gl::ELEMENT_ARRAY_BUFFER);
+        }
+    });
+}
+
+pub fn get_stats() -> StateCacheStats {
+    STATE.with(|state| state.borrow().stats)
+}
+
+pub fn reset_stats() {
+    STATE.with(|state| state.borrow_mut().stats = StateCacheStats::default());
+}
@@ -0,0 +1,185 @@
+use super::buffer::{Buffer, BufferTarget, BufferUsage};
+use super::deletion_queue::{self, GlObject};
+use crate::error::RlibError;
+use bytemuck::Pod;
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+extern crate log;
+
+pub const REGION_COUNT: usize = 3;
+
+// How long a single `ClientWaitSync` blocks before the wait is retried.
+const FENCE_WAIT_TIMEOUT_NS: u64 = 1_000_000;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StreamBufferStats {
+    pub writes: u64,
+    pub bytes_written: u64,
+    pub region_switches: u64,
+    // Times the CPU caught up with a region the GPU was still reading.
+    pub stalls: u64,
+    pub stall_time: Duration,
+}
+
+thread_local! {
+    static STATS: RefCell<StreamBufferStats> = RefCell::new(StreamBufferStats::default());
+}
+
+// A persistently mapped ring of `REGION_COUNT` regions. Writes go straight
+// into the mapping, and a region is only reused once the fence placed when
+// leaving it has signalled, so uploads never wait on draws still in flight
+// unless the GPU is a whole ring behind.
+pub struct StreamBuffer<T: Pod> {
+    buffer: Buffer<T>,
+    mapping: *mut T,
+    region_len: usize,
+    region: usize,
+    cursor: usize,
+    fences: [Option<gl::types::GLsync>; REGION_COUNT],
+}
+
+impl<T: Pod> StreamBuffer<T> {
+    // `region_len` is the most elements that can be written between two
+    // fences, and so also the largest single write.
+    #[track_caller]
+    pub fn new(target: BufferTarget, region_len: usize) -> Result<StreamBuffer<T>, RlibError> {
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+        let buffer = Buffer::with_storage_flags(
+            target,
+            BufferUsage::Stream,
+            region_len * REGION_COUNT,
+            flags,
+        )?;
+
+        let mapping = unsafe {
+            gl::MapNamedBufferRange(buffer.get_handle(), 0, buffer.get_size(), flags) as *mut T
+        };
+
+        if mapping.is_null() {
+            return Err(RlibError::GlResource("stream buffer mapping".to_string()));
+        }
+
+        Ok(StreamBuffer {
+            buffer,
+            mapping,
+            region_len,
+            region: 0,
+            cursor: 0,
+            fences: [None; REGION_COUNT],
+        })
+    }
+
+    // Returns the element offset the data starts at, to be used as the base
+    // vertex or binding offset of the draw that reads it.
+    pub fn write(&mut self, data: &[T]) -> usize {
+        assert!(
+            data.len() <= self.region_len,
+            "StreamBuffer::write() of {} elements exceeds the region length {}",
+            data.len(),
+            self.region_len
+        );
+
+        if self.cursor + data.len() > self.region_len {
+            self.next_region();
+        }
+
+        let offset = self.region * self.region_len + self.cursor;
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.mapping.add(offset), data.len());
+        }
+
+        self.cursor += data.len();
+
+        STATS.with(|stats| {
+            let mut stats = stats.borrow_mut();
+            stats.writes += 1;
+            stats.bytes_written += std::mem::size_of_val(data) as u64;
+        });
+
+        offset
+    }
+
+    // Fences everything issued against the current region and moves on to the
+    // next one, waiting for the GPU to finish with it if necessary.
+    fn next_region(&mut self) {
+        unsafe {
+            let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.fences[self.region] = Some(fence);
+        }
+
+        self.region = (self.region + 1) % REGION_COUNT;
+        self.cursor = 0;
+
+        STATS.with(|stats| stats.borrow_mut().region_switches += 1);
+
+        if let Some(fence) = self.fences[self.region].take() {
+            wait_for_fence(fence);
+        }
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.buffer.set_label(label);
+    }
+
+    pub fn get_region_len(&self) -> usize {
+        self.region_len
+    }
+
+    // For attaching to a vertex array; the contents can only be written
+    // through `write`.
+    pub fn get_buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+}
+
+impl<T: Pod> Drop for StreamBuffer<T> {
+    fn drop(&mut self) {
+        // Deleting the buffer unmaps it, so only the fences need cleaning up.
+        for fence in self.fences.iter_mut().filter_map(Option::take) {
+            deletion_queue::queue_delete(GlObject::Fence(fence as usize));
+        }
+    }
+}
+
+fn wait_for_fence(fence: gl::types::GLsync) {
+    let start = Instant::now();
+    let mut stalled = false;
+
+    loop {
+        let timeout = if stalled { FENCE_WAIT_TIMEOUT_NS } else { 0 };
+        let result = unsafe { gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) };
+
+        match result {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => break,
+            gl::TIMEOUT_EXPIRED => stalled = true,
+            _ => {
+                error!("Waiting on a stream buffer fence failed");
+                break;
+            }
+        }
+    }
+
+    unsafe {
+        gl::DeleteSync(fence);
+    }
+
+    if stalled {
+        let waited = start.elapsed();
+
+        STATS.with(|stats| {
+            let mut stats = stats.borrow_mut();
+            stats.stalls += 1;
+            stats.stall_time += waited;
+        });
+    }
+}
+
+pub fn get_stats() -> StreamBufferStats {
+    STATS.with(|stats| *stats.borrow())
+}
+
+pub fn reset_stats() {
+    STATS.with(|stats| *stats.borrow_mut() = StreamBufferStats::default());
+}
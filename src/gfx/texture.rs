@@ -1,21 +1,35 @@
 extern crate log;
+use super::debug;
+use super::deletion_queue::{self, GlObject};
+use super::resource_tracker::{self, ResourceKind};
+use super::state_cache;
+use crate::error::RlibError;
 use image::{io::Reader as ImageReader, GenericImageView};
 use std::cell::Cell;
 use std::cmp;
 use std::rc::Rc;
 
 pub(crate) struct NativeTexture {
-    handle: u32,
+    // Reloading swaps in a new GL object and image behind every clone.
+    handle: Cell<u32>,
     texture_type: u32,
-    width: u32,
-    height: u32,
+    width: Cell<u32>,
+    height: Cell<u32>,
     depth: u32,
     mip_levels: Cell<u32>,
+    path: Option<String>,
+}
+
+impl Drop for NativeTexture {
+    fn drop(&mut self) {
+        resource_tracker::unregister(ResourceKind::Texture, self.handle.get());
+        deletion_queue::queue_delete(GlObject::Texture(self.handle.get()));
+    }
 }
 
 impl PartialEq for NativeTexture {
     fn eq(&self, other: &NativeTexture) -> bool {
-        self.handle == other.handle
+        self.handle.get() == other.handle.get()
     }
 }
 
@@ -31,90 +45,180 @@ impl PartialEq for Texture {
 }
 
 impl Texture {
-    pub fn from_file(path: &str) -> Option<Texture> {
+    #[track_caller]
+    pub fn from_file(path: &str) -> Result<Texture, RlibError> {
+        let image = ImageReader::open(path)
+            .map_err(|source| RlibError::Io {
+                path: Some(path.into()),
+                source,
+            })?
+            .decode()?;
+
         let mut handle: u32 = 0;
         let texture_type = gl::TEXTURE_2D;
 
         unsafe {
-            gl::GenTextures(1, &mut handle);
+            gl::CreateTextures(texture_type, 1, &mut handle);
         }
 
         if handle == 0 {
-            None
+            Err(RlibError::GlResource(format!("texture for {}", path)))
         } else {
-            let image = ImageReader::open(path).unwrap().decode().unwrap();
             let image_dims = image.dimensions();
+            let storage_levels = mip_chain_length(image_dims.0, image_dims.1);
 
             unsafe {
-                gl::BindTexture(texture_type, handle);
-
-                gl::TexImage2D(
-                    texture_type,
-                    0,
-                    gl::RGBA as i32,
+                // Storage is immutable, so the whole mip chain is allocated up
+                // front and sampling is capped at the base level until
+                // `generate_mipmaps` fills the rest in.
+                gl::TextureStorage2D(
+                    handle,
+                    storage_levels as i32,
+                    gl::RGBA8,
                     image_dims.0 as i32,
                     image_dims.1 as i32,
+                );
+                gl::TextureSubImage2D(
+                    handle,
+                    0,
                     0,
+                    0,
+                    image_dims.0 as i32,
+                    image_dims.1 as i32,
                     gl::RGBA,
                     gl::UNSIGNED_BYTE,
                     image.into_rgba8().as_raw().as_ptr() as *const std::os::raw::c_void,
                 );
+                gl::TextureParameteri(handle, gl::TEXTURE_MAX_LEVEL, 0);
 
-                gl::TexParameteri(texture_type, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-                gl::TexParameteri(texture_type, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+                gl::TextureParameteri(handle, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+                gl::TextureParameteri(handle, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
 
-                gl::TextureParameteri(texture_type, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-                gl::TextureParameteri(texture_type, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-                gl::TextureParameteri(texture_type, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
-
-                gl::BindTexture(texture_type, 0);
+                gl::TextureParameteri(handle, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(handle, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                gl::TextureParameteri(handle, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
             }
 
-            Some(Texture {
+            resource_tracker::register(
+                ResourceKind::Texture,
+                handle,
+                Some((image_dims.0, image_dims.1, 1)),
+                1,
+                resource_tracker::texture_byte_size(image_dims.0, image_dims.1, 1, storage_levels),
+                std::panic::Location::caller(),
+            );
+
+            let texture = Texture {
                 handle: Rc::new(NativeTexture {
-                    handle,
+                    handle: Cell::new(handle),
                     texture_type,
-                    width: image_dims.0,
-                    height: image_dims.1,
+                    width: Cell::new(image_dims.0),
+                    height: Cell::new(image_dims.1),
                     depth: 1,
                     mip_levels: Cell::new(1),
+                    path: Some(path.to_string()),
                 }),
-            })
+            };
+            texture.set_label(path);
+
+            Ok(texture)
         }
     }
 
-    pub fn bind(&self, slot: u32) {
-        unsafe {
-            gl::ActiveTexture(gl::TEXTURE0 + slot);
-            gl::BindTexture(self.handle.texture_type, self.handle.handle);
+    // Re-reads the file behind every clone of this texture. Filtering, wrap
+    // modes and generated mipmaps carry over; on failure the old image stays.
+    pub fn reload(&self) -> Result<(), RlibError> {
+        let path = self.handle.path.as_ref().ok_or_else(|| RlibError::Io {
+            path: None,
+            source: std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "texture wasn't loaded from a file",
+            ),
+        })?;
+
+        let mut fresh = Texture::from_file(path)?;
+
+        if self.get_mip_levels() > 1 {
+            fresh.generate_mipmaps();
+        }
+
+        let parameters = [
+            gl::TEXTURE_MIN_FILTER,
+            gl::TEXTURE_MAG_FILTER,
+            gl::TEXTURE_WRAP_S,
+            gl::TEXTURE_WRAP_T,
+            gl::TEXTURE_WRAP_R,
+        ];
+
+        for parameter in parameters {
+            let mut value: i32 = 0;
+
+            unsafe {
+                gl::GetTextureParameteriv(self.get_handle(), parameter, &mut value);
+                gl::TextureParameteri(fresh.get_handle(), parameter, value);
+            }
+        }
+
+        let (old, new) = (&self.handle, &fresh.handle);
+        old.handle.swap(&new.handle);
+        old.width.swap(&new.width);
+        old.height.swap(&new.height);
+        old.mip_levels.swap(&new.mip_levels);
+
+        // `fresh` now owns the old GL object and deletes it when dropped.
+        let label = resource_tracker::swap_identity(
+            ResourceKind::Texture,
+            new.handle.get(),
+            old.handle.get(),
+        );
+        if let Some(label) = label {
+            debug::label_object(gl::TEXTURE, old.handle.get(), &label);
         }
+
+        Ok(())
+    }
+
+    pub fn bind(&self, slot: u32) {
+        state_cache::bind_texture(slot, self.handle.texture_type, self.handle.handle.get());
     }
 
     pub fn generate_mipmaps(&mut self) {
-        self.bind(0);
-        // self.handle.mip_levels = 0;
-        self.handle.mip_levels.set(
-            ((cmp::max(self.handle.width, self.handle.height) as f32)
-                .log2()
-                .floor() as u32)
-                + 1,
-        );
+        let mip_levels = mip_chain_length(self.get_width(), self.get_height());
+        self.handle.mip_levels.set(mip_levels);
 
         unsafe {
-            gl::GenerateMipmap(self.handle.texture_type);
+            gl::TextureParameteri(
+                self.get_handle(),
+                gl::TEXTURE_MAX_LEVEL,
+                mip_levels as i32 - 1,
+            );
+            gl::GenerateTextureMipmap(self.get_handle());
         }
+
+        resource_tracker::update(ResourceKind::Texture, self.get_handle(), |info| {
+            info.mip_levels = mip_levels;
+        });
+    }
+
+    pub fn set_label(&self, label: &str) {
+        debug::label_object(gl::TEXTURE, self.get_handle(), label);
+        resource_tracker::set_label(ResourceKind::Texture, self.get_handle(), label);
     }
 
     pub fn get_handle(&self) -> u32 {
-        self.handle.handle
+        self.handle.handle.get()
+    }
+
+    pub fn get_path(&self) -> Option<&str> {
+        self.handle.path.as_deref()
     }
 
     pub fn get_width(&self) -> u32 {
-        self.handle.width
+        self.handle.width.get()
     }
 
     pub fn get_height(&self) -> u32 {
-        self.handle.height
+        self.handle.height.get()
     }
 
     pub fn get_depth(&self) -> u32 {
@@ -126,27 +230,21 @@ impl Texture {
     }
 
     pub fn set_min_mag_filters(&self, min_filter: u32, mag_filter: u32) {
-        self.bind(0);
         unsafe {
-            gl::TexParameteri(
-                self.handle.texture_type,
-                gl::TEXTURE_MIN_FILTER,
-                min_filter as i32,
-            );
-            gl::TexParameteri(
-                self.handle.texture_type,
-                gl::TEXTURE_MAG_FILTER,
-                mag_filter as i32,
-            );
+            gl::TextureParameteri(self.get_handle(), gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TextureParameteri(self.get_handle(), gl::TEXTURE_MAG_FILTER, mag_filter as i32);
         }
     }
 
     pub fn set_wrap_modes(&self, s_wrap: u32, t_wrap: u32, r_wrap: u32) {
-        self.bind(0);
         unsafe {
-            gl::TextureParameteri(self.handle.texture_type, gl::TEXTURE_WRAP_S, s_wrap as i32);
-            gl::TextureParameteri(self.handle.texture_type, gl::TEXTURE_WRAP_T, t_wrap as i32);
-            gl::TextureParameteri(self.handle.texture_type, gl::TEXTURE_WRAP_R, r_wrap as i32);
+            gl::TextureParameteri(self.get_handle(), gl::TEXTURE_WRAP_S, s_wrap as i32);
+            gl::TextureParameteri(self.get_handle(), gl::TEXTURE_WRAP_T, t_wrap as i32);
+            gl::TextureParameteri(self.get_handle(), gl::TEXTURE_WRAP_R, r_wrap as i32);
         }
     }
 }
+
+fn mip_chain_length(width: u32, height: u32) -> u32 {
+    ((cmp::max(width, height).max(1) as f32).log2().floor() as u32) + 1
+}
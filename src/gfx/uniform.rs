@@ -0,0 +1,443 @@
+use super::color::Color;
+use super::shader::ShaderProgram;
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+pub use rlib_derive::Uniforms;
+
+// An active uniform as reported by the driver. Arrays are keyed by their name
+// without the trailing `[0]`, with `size` holding the element count.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UniformInfo {
+    pub location: i32,
+    pub gl_type: u32,
+    pub size: i32,
+}
+
+// Sets a sampler (or image) uniform to a texture unit, as opposed to a plain
+// `i32` which is also accepted by int uniforms.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TextureUnit(pub u32);
+
+pub trait UniformValue {
+    // Whether the value can be uploaded to a uniform of this GL type.
+    fn is_compatible(&self, gl_type: u32) -> bool;
+
+    // Number of array elements the value covers.
+    fn get_count(&self) -> usize {
+        1
+    }
+
+    fn upload(&self, program: u32, location: i32);
+}
+
+// Anything that can also be uploaded as an array with the `*v` setters.
+pub trait UniformElement: Sized {
+    fn is_compatible_type(gl_type: u32) -> bool;
+    fn upload_slice(values: &[Self], program: u32, location: i32);
+}
+
+pub trait Uniforms {
+    fn apply(&self, program: &ShaderProgram);
+}
+
+impl<T: UniformElement> UniformValue for T {
+    fn is_compatible(&self, gl_type: u32) -> bool {
+        T::is_compatible_type(gl_type)
+    }
+
+    fn upload(&self, program: u32, location: i32) {
+        T::upload_slice(std::slice::from_ref(self), program, location);
+    }
+}
+
+impl<T: UniformElement> UniformValue for [T] {
+    fn is_compatible(&self, gl_type: u32) -> bool {
+        T::is_compatible_type(gl_type)
+    }
+
+    fn get_count(&self) -> usize {
+        self.len()
+    }
+
+    fn upload(&self, program: u32, location: i32) {
+        if !self.is_empty() {
+            T::upload_slice(self, program, location);
+        }
+    }
+}
+
+impl<T: UniformElement, const N: usize> UniformValue for [T; N] {
+    fn is_compatible(&self, gl_type: u32) -> bool {
+        T::is_compatible_type(gl_type)
+    }
+
+    fn get_count(&self) -> usize {
+        N
+    }
+
+    fn upload(&self, program: u32, location: i32) {
+        self[..].upload(program, location);
+    }
+}
+
+impl UniformElement for f32 {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::FLOAT
+    }
+
+    fn upload_slice(values: &[f32], program: u32, location: i32) {
+        unsafe { gl::ProgramUniform1fv(program, location, values.len() as i32, values.as_ptr()) }
+    }
+}
+
+impl UniformElement for i32 {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::INT || gl_type == gl::BOOL || is_sampler_type(gl_type)
+    }
+
+    fn upload_slice(values: &[i32], program: u32, location: i32) {
+        unsafe { gl::ProgramUniform1iv(program, location, values.len() as i32, values.as_ptr()) }
+    }
+}
+
+impl UniformElement for u32 {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::UNSIGNED_INT || gl_type == gl::BOOL
+    }
+
+    fn upload_slice(values: &[u32], program: u32, location: i32) {
+        unsafe { gl::ProgramUniform1uiv(program, location, values.len() as i32, values.as_ptr()) }
+    }
+}
+
+impl UniformElement for bool {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::BOOL
+    }
+
+    fn upload_slice(values: &[bool], program: u32, location: i32) {
+        let values: Vec<i32> = values.iter().map(|value| *value as i32).collect();
+        i32::upload_slice(&values, program, location);
+    }
+}
+
+impl UniformElement for TextureUnit {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        is_sampler_type(gl_type)
+    }
+
+    fn upload_slice(values: &[TextureUnit], program: u32, location: i32) {
+        let values: Vec<i32> = values.iter().map(|unit| unit.0 as i32).collect();
+        i32::upload_slice(&values, program, location);
+    }
+}
+
+impl UniformElement for Vec2 {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::FLOAT_VEC2
+    }
+
+    fn upload_slice(values: &[Vec2], program: u32, location: i32) {
+        unsafe {
+            gl::ProgramUniform2fv(
+                program,
+                location,
+                values.len() as i32,
+                values.as_ptr() as *const f32,
+            )
+        }
+    }
+}
+
+impl UniformElement for Vec3 {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::FLOAT_VEC3
+    }
+
+    fn upload_slice(values: &[Vec3], program: u32, location: i32) {
+        unsafe {
+            gl::ProgramUniform3fv(
+                program,
+                location,
+                values.len() as i32,
+                values.as_ptr() as *const f32,
+            )
+        }
+    }
+}
+
+impl UniformElement for Vec4 {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::FLOAT_VEC4
+    }
+
+    fn upload_slice(values: &[Vec4], program: u32, location: i32) {
+        unsafe {
+            gl::ProgramUniform4fv(
+                program,
+                location,
+                values.len() as i32,
+                values.as_ptr() as *const f32,
+            )
+        }
+    }
+}
+
+impl UniformElement for Color {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::FLOAT_VEC4
+    }
+
+    fn upload_slice(values: &[Color], program: u32, location: i32) {
+        let values: Vec<Vec4> = values
+            .iter()
+            .map(|c| Vec4::new(c.r, c.g, c.b, c.a))
+            .collect();
+        Vec4::upload_slice(&values, program, location);
+    }
+}
+
+impl UniformElement for Mat3 {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::FLOAT_MAT3
+    }
+
+    fn upload_slice(values: &[Mat3], program: u32, location: i32) {
+        unsafe {
+            gl::ProgramUniformMatrix3fv(
+                program,
+                location,
+                values.len() as i32,
+                gl::FALSE,
+                values.as_ptr() as *const f32,
+            )
+        }
+    }
+}
+
+impl UniformElement for Mat4 {
+    fn is_compatible_type(gl_type: u32) -> bool {
+        gl_type == gl::FLOAT_MAT4
+    }
+
+    fn upload_slice(values: &[Mat4], program: u32, location: i32) {
+        unsafe {
+            gl::ProgramUniformMatrix4fv(
+                program,
+                location,
+                values.len() as i32,
+                gl::FALSE,
+                values.as_ptr() as *const f32,
+            )
+        }
+    }
+}
+
+pub(crate) fn is_sampler_type(gl_type: u32) -> bool {
+    matches!(
+        gl_type,
+        gl::SAMPLER_1D
+            | gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_1D_SHADOW
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_1D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_CUBE_MAP_ARRAY
+            | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW
+            | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_2D_RECT
+            | gl::SAMPLER_2D_RECT_SHADOW
+            | gl::INT_SAMPLER_1D
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE
+            | gl::INT_SAMPLER_1D_ARRAY
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::INT_SAMPLER_2D_MULTISAMPLE
+            | gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::INT_SAMPLER_BUFFER
+            | gl::INT_SAMPLER_2D_RECT
+            | gl::UNSIGNED_INT_SAMPLER_1D
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_BUFFER
+            | gl::UNSIGNED_INT_SAMPLER_2D_RECT
+            | gl::IMAGE_1D
+            | gl::IMAGE_2D
+            | gl::IMAGE_3D
+            | gl::IMAGE_CUBE
+            | gl::IMAGE_1D_ARRAY
+            | gl::IMAGE_2D_ARRAY
+            | gl::IMAGE_BUFFER
+            | gl::IMAGE_2D_RECT
+    )
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Scalar {
+    Float,
+    Int,
+    Uint,
+}
+
+// Scalar type, number of components and, for matrices, columns and rows.
+type UniformShape = (Scalar, usize, Option<(usize, usize)>);
+
+// How a uniform of this type is read back and written. `None` for types that
+// can't be copied, such as doubles.
+fn get_uniform_shape(gl_type: u32) -> Option<UniformShape> {
+    let shape = match gl_type {
+        gl::FLOAT => (Scalar::Float, 1, None),
+        gl::FLOAT_VEC2 => (Scalar::Float, 2, None),
+        gl::FLOAT_VEC3 => (Scalar::Float, 3, None),
+        gl::FLOAT_VEC4 => (Scalar::Float, 4, None),
+        gl::FLOAT_MAT2 => (Scalar::Float, 4, Some((2, 2))),
+        gl::FLOAT_MAT3 => (Scalar::Float, 9, Some((3, 3))),
+        gl::FLOAT_MAT4 => (Scalar::Float, 16, Some((4, 4))),
+        gl::FLOAT_MAT2x3 => (Scalar::Float, 6, Some((2, 3))),
+        gl::FLOAT_MAT2x4 => (Scalar::Float, 8, Some((2, 4))),
+        gl::FLOAT_MAT3x2 => (Scalar::Float, 6, Some((3, 2))),
+        gl::FLOAT_MAT3x4 => (Scalar::Float, 12, Some((3, 4))),
+        gl::FLOAT_MAT4x2 => (Scalar::Float, 8, Some((4, 2))),
+        gl::FLOAT_MAT4x3 => (Scalar::Float, 12, Some((4, 3))),
+        gl::INT | gl::BOOL => (Scalar::Int, 1, None),
+        gl::INT_VEC2 | gl::BOOL_VEC2 => (Scalar::Int, 2, None),
+        gl::INT_VEC3 | gl::BOOL_VEC3 => (Scalar::Int, 3, None),
+        gl::INT_VEC4 | gl::BOOL_VEC4 => (Scalar::Int, 4, None),
+        gl::UNSIGNED_INT => (Scalar::Uint, 1, None),
+        gl::UNSIGNED_INT_VEC2 => (Scalar::Uint, 2, None),
+        gl::UNSIGNED_INT_VEC3 => (Scalar::Uint, 3, None),
+        gl::UNSIGNED_INT_VEC4 => (Scalar::Uint, 4, None),
+        _ if is_sampler_type(gl_type) => (Scalar::Int, 1, None),
+        _ => return None,
+    };
+
+    Some(shape)
+}
+
+// Copies the current value of one uniform (a single array element) between
+// programs. Returns false for types that can't be copied.
+pub(crate) fn copy_uniform(
+    gl_type: u32,
+    from_program: u32,
+    from_location: i32,
+    to_program: u32,
+    to_location: i32,
+) -> bool {
+    let (scalar, components, matrix) = match get_uniform_shape(gl_type) {
+        Some(shape) => shape,
+        None => return false,
+    };
+
+    unsafe {
+        match scalar {
+            Scalar::Float => {
+                let mut values = [0.0f32; 16];
+                gl::GetUniformfv(from_program, from_location, values.as_mut_ptr());
+                let values = values.as_ptr();
+
+                match (matrix, components) {
+                    (Some((2, 2)), _) => {
+                        gl::ProgramUniformMatrix2fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (Some((3, 3)), _) => {
+                        gl::ProgramUniformMatrix3fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (Some((4, 4)), _) => {
+                        gl::ProgramUniformMatrix4fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (Some((2, 3)), _) => {
+                        gl::ProgramUniformMatrix2x3fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (Some((2, 4)), _) => {
+                        gl::ProgramUniformMatrix2x4fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (Some((3, 2)), _) => {
+                        gl::ProgramUniformMatrix3x2fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (Some((3, 4)), _) => {
+                        gl::ProgramUniformMatrix3x4fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (Some((4, 2)), _) => {
+                        gl::ProgramUniformMatrix4x2fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (Some(_), _) => {
+                        gl::ProgramUniformMatrix4x3fv(to_program, to_location, 1, gl::FALSE, values)
+                    }
+                    (None, 1) => gl::ProgramUniform1fv(to_program, to_location, 1, values),
+                    (None, 2) => gl::ProgramUniform2fv(to_program, to_location, 1, values),
+                    (None, 3) => gl::ProgramUniform3fv(to_program, to_location, 1, values),
+                    (None, _) => gl::ProgramUniform4fv(to_program, to_location, 1, values),
+                }
+            }
+            Scalar::Int => {
+                let mut values = [0i32; 4];
+                gl::GetUniformiv(from_program, from_location, values.as_mut_ptr());
+                let values = values.as_ptr();
+
+                match components {
+                    1 => gl::ProgramUniform1iv(to_program, to_location, 1, values),
+                    2 => gl::ProgramUniform2iv(to_program, to_location, 1, values),
+                    3 => gl::ProgramUniform3iv(to_program, to_location, 1, values),
+                    _ => gl::ProgramUniform4iv(to_program, to_location, 1, values),
+                }
+            }
+            Scalar::Uint => {
+                let mut values = [0u32; 4];
+                gl::GetUniformuiv(from_program, from_location, values.as_mut_ptr());
+                let values = values.as_ptr();
+
+                match components {
+                    1 => gl::ProgramUniform1uiv(to_program, to_location, 1, values),
+                    2 => gl::ProgramUniform2uiv(to_program, to_location, 1, values),
+                    3 => gl::ProgramUniform3uiv(to_program, to_location, 1, values),
+                    _ => gl::ProgramUniform4uiv(to_program, to_location, 1, values),
+                }
+            }
+        }
+    }
+
+    true
+}
+
+pub(crate) fn uniform_type_name(gl_type: u32) -> String {
+    let name = match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        _ if is_sampler_type(gl_type) => "sampler",
+        _ => return format!("0x{:04X}", gl_type),
+    };
+
+    name.to_string()
+}
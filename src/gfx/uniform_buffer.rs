@@ -0,0 +1,278 @@
+use super::buffer::{Buffer, BufferTarget, BufferUsage};
+use crate::error::RlibError;
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec2};
+use std::collections::HashMap;
+
+// A macro so the GLSL below can be built from the same literal.
+macro_rules! camera_block_binding {
+    () => {
+        0
+    };
+}
+
+pub const CAMERA_BLOCK_BINDING: u32 = camera_block_binding!();
+pub const CAMERA_BLOCK_NAME: &str = "Camera";
+
+// Declaration matching `CameraBlock`, for shaders to paste in. Programs that
+// declare a `Camera` block get it bound to `CAMERA_BLOCK_BINDING`.
+pub const CAMERA_BLOCK_GLSL: &str = concat!(
+    "
+layout (std140, binding = ",
+    camera_block_binding!(),
+    ") uniform Camera {
+    mat4 u_view;
+    mat4 u_projection;
+    vec2 u_viewport_size;
+    float u_time;
+};
+"
+);
+
+// Laid out to match `CAMERA_BLOCK_GLSL` under std140.
+#[derive(Clone, Copy, Pod, Zeroable, Debug)]
+#[repr(C)]
+pub struct CameraBlock {
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub viewport_size: Vec2,
+    pub time: f32,
+    pub _padding: f32,
+}
+
+impl Default for CameraBlock {
+    fn default() -> CameraBlock {
+        CameraBlock {
+            view: Mat4::IDENTITY,
+            projection: Mat4::IDENTITY,
+            viewport_size: Vec2::ZERO,
+            time: 0.0,
+            _padding: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlockKind {
+    Uniform,
+    ShaderStorage,
+}
+
+impl BlockKind {
+    fn interface(self) -> u32 {
+        match self {
+            BlockKind::Uniform => gl::UNIFORM_BLOCK,
+            BlockKind::ShaderStorage => gl::SHADER_STORAGE_BLOCK,
+        }
+    }
+
+    fn member_interface(self) -> u32 {
+        match self {
+            BlockKind::Uniform => gl::UNIFORM,
+            BlockKind::ShaderStorage => gl::BUFFER_VARIABLE,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlockMember {
+    pub name: String,
+    pub gl_type: u32,
+    pub offset: i32,
+    // 0 for a runtime-sized array at the end of a storage block.
+    pub array_size: i32,
+    pub array_stride: i32,
+    pub matrix_stride: i32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlockInfo {
+    pub name: String,
+    pub kind: BlockKind,
+    pub index: u32,
+    pub binding: u32,
+    pub data_size: i32,
+    pub members: Vec<BlockMember>,
+}
+
+// A single `T` in a uniform buffer, read by whichever blocks are bound to the
+// same binding point.
+pub struct UniformBuffer<T: Pod> {
+    buffer: Buffer<T>,
+}
+
+impl<T: Pod> UniformBuffer<T> {
+    #[track_caller]
+    pub fn new(value: &T) -> Result<UniformBuffer<T>, RlibError> {
+        Ok(UniformBuffer {
+            buffer: Buffer::from_slice(
+                BufferTarget::Uniform,
+                BufferUsage::Dynamic,
+                std::slice::from_ref(value),
+            )?,
+        })
+    }
+
+    pub fn set(&self, value: &T) {
+        self.buffer.update(0..1, std::slice::from_ref(value));
+    }
+
+    pub fn bind_to(&self, binding: u32) {
+        self.buffer.bind_base(binding);
+    }
+
+    pub fn set_label(&self, label: &str) {
+        self.buffer.set_label(label);
+    }
+
+    pub fn get_buffer(&self) -> &Buffer<T> {
+        &self.buffer
+    }
+}
+
+pub(crate) fn reflect_blocks(program: u32, kind: BlockKind) -> HashMap<String, BlockInfo> {
+    let mut blocks = HashMap::new();
+    let interface = kind.interface();
+    let member_interface = kind.member_interface();
+
+    let mut block_count: i32 = 0;
+    unsafe {
+        gl::GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut block_count);
+    }
+
+    for index in 0..block_count as u32 {
+        let block_properties = [
+            gl::BUFFER_BINDING,
+            gl::BUFFER_DATA_SIZE,
+            gl::NUM_ACTIVE_VARIABLES,
+        ];
+        let [binding, data_size, member_count] =
+            get_resource_properties(program, interface, index, &block_properties);
+
+        let mut member_indices = vec![0i32; member_count as usize];
+        if member_count > 0 {
+            unsafe {
+                gl::GetProgramResourceiv(
+                    program,
+                    interface,
+                    index,
+                    1,
+                    &gl::ACTIVE_VARIABLES,
+                    member_count,
+                    std::ptr::null_mut(),
+                    member_indices.as_mut_ptr(),
+                );
+            }
+        }
+
+        let member_properties = [
+            gl::TYPE,
+            gl::OFFSET,
+            gl::ARRAY_SIZE,
+            gl::ARRAY_STRIDE,
+            gl::MATRIX_STRIDE,
+        ];
+
+        let mut members: Vec<BlockMember> = member_indices
+            .iter()
+            .map(|member| {
+                let [gl_type, offset, array_size, array_stride, matrix_stride] =
+                    get_resource_properties(
+                        program,
+                        member_interface,
+                        *member as u32,
+                        &member_properties,
+                    );
+
+                BlockMember {
+                    name: get_resource_name(program, member_interface, *member as u32),
+                    gl_type: gl_type as u32,
+                    offset,
+                    array_size,
+                    array_stride,
+                    matrix_stride,
+                }
+            })
+            .collect();
+        members.sort_by_key(|member| member.offset);
+
+        let name = get_resource_name(program, interface, index);
+
+        blocks.insert(
+            name.clone(),
+            BlockInfo {
+                name,
+                kind,
+                index,
+                binding: binding as u32,
+                data_size,
+                members,
+            },
+        );
+    }
+
+    blocks
+}
+
+fn get_resource_properties<const N: usize>(
+    program: u32,
+    interface: u32,
+    index: u32,
+    properties: &[u32; N],
+) -> [i32; N] {
+    let mut values = [0i32; N];
+
+    unsafe {
+        gl::GetProgramResourceiv(
+            program,
+            interface,
+            index,
+            N as i32,
+            properties.as_ptr(),
+            N as i32,
+            std::ptr::null_mut(),
+            values.as_mut_ptr(),
+        );
+    }
+
+    values
+}
+
+fn get_resource_name(program: u32, interface: u32, index: u32) -> String {
+    let mut max_length: i32 = 0;
+    unsafe {
+        gl::GetProgramInterfaceiv(program, interface, gl::MAX_NAME_LENGTH, &mut max_length);
+    }
+
+    let mut name = vec![0u8; max_length.max(1) as usize];
+    let mut length: i32 = 0;
+
+    unsafe {
+        gl::GetProgramResourceName(
+            program,
+            interface,
+            index,
+            name.len() as i32,
+            &mut length,
+            name.as_mut_ptr() as *mut gl::types::GLchar,
+        );
+    }
+
+    name.truncate(length as usize);
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_block_glsl_matches_the_rust_side() {
+        let binding = format!("binding = {})", CAMERA_BLOCK_BINDING);
+        assert!(CAMERA_BLOCK_GLSL.contains(&binding));
+        assert!(CAMERA_BLOCK_GLSL.contains(&format!("uniform {} {{", CAMERA_BLOCK_NAME)));
+
+        // Two mat4s, a vec2 and a float, rounded up to a vec4 under std140.
+        assert_eq!(std::mem::size_of::<CameraBlock>(), 144);
+    }
+}
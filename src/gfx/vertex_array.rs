@@ -0,0 +1,341 @@
+use super::buffer::{Buffer, IndexType, NativeBuffer};
+use super::debug;
+use super::deletion_queue::{self, GlObject};
+use super::resource_tracker::{self, ResourceKind};
+use super::state_cache;
+use crate::error::RlibError;
+use bytemuck::Pod;
+use glam::{Vec2, Vec3, Vec4};
+pub use rlib_derive::Vertex;
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AttributeType {
+    F32,
+    I32,
+    U32,
+    I16,
+    U16,
+    I8,
+    U8,
+}
+
+impl AttributeType {
+    pub fn to_gl(self) -> u32 {
+        match self {
+            AttributeType::F32 => gl::FLOAT,
+            AttributeType::I32 => gl::INT,
+            AttributeType::U32 => gl::UNSIGNED_INT,
+            AttributeType::I16 => gl::SHORT,
+            AttributeType::U16 => gl::UNSIGNED_SHORT,
+            AttributeType::I8 => gl::BYTE,
+            AttributeType::U8 => gl::UNSIGNED_BYTE,
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        self != AttributeType::F32
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub components: i32,
+    pub attribute_type: AttributeType,
+    // Integer attributes that aren't normalized reach the shader as ints.
+    pub normalized: bool,
+    pub offset: u32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VertexLayout {
+    pub stride: i32,
+    // 0 advances per vertex, N advances once every N instances.
+    pub divisor: u32,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    pub fn new(stride: usize) -> VertexLayout {
+        VertexLayout {
+            stride: stride as i32,
+            divisor: 0,
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn with_divisor(mut self, divisor: u32) -> VertexLayout {
+        self.divisor = divisor;
+        self
+    }
+
+    pub fn attribute(
+        mut self,
+        location: u32,
+        components: i32,
+        attribute_type: AttributeType,
+        offset: usize,
+    ) -> VertexLayout {
+        self.attributes.push(VertexAttribute {
+            location,
+            components,
+            attribute_type,
+            normalized: false,
+            offset: offset as u32,
+        });
+        self
+    }
+
+    pub fn normalized_attribute(
+        mut self,
+        location: u32,
+        components: i32,
+        attribute_type: AttributeType,
+        offset: usize,
+    ) -> VertexLayout {
+        self.attributes.push(VertexAttribute {
+            location,
+            components,
+            attribute_type,
+            normalized: true,
+            offset: offset as u32,
+        });
+        self
+    }
+}
+
+pub trait Vertex: Pod {
+    fn layout() -> VertexLayout;
+}
+
+// How a field type is laid out as a vertex attribute, used by
+// `#[derive(Vertex)]`.
+pub trait AttributeFormat {
+    const COMPONENTS: i32;
+    const TYPE: AttributeType;
+}
+
+macro_rules! attribute_formats {
+    ($($ty:ty => ($components:expr, $attribute_type:ident)),* $(,)?) => {
+        $(
+            impl AttributeFormat for $ty {
+                const COMPONENTS: i32 = $components;
+                const TYPE: AttributeType = AttributeType::$attribute_type;
+            }
+        )*
+    };
+}
+
+attribute_formats! {
+    f32 => (1, F32),
+    [f32; 2] => (2, F32),
+    [f32; 3] => (3, F32),
+    [f32; 4] => (4, F32),
+    Vec2 => (2, F32),
+    Vec3 => (3, F32),
+    Vec4 => (4, F32),
+    i32 => (1, I32),
+    [i32; 2] => (2, I32),
+    [i32; 3] => (3, I32),
+    [i32; 4] => (4, I32),
+    u32 => (1, U32),
+    [u32; 2] => (2, U32),
+    [u32; 3] => (3, U32),
+    [u32; 4] => (4, U32),
+    i16 => (1, I16),
+    [i16; 2] => (2, I16),
+    [i16; 4] => (4, I16),
+    u16 => (1, U16),
+    [u16; 2] => (2, U16),
+    [u16; 4] => (4, U16),
+    i8 => (1, I8),
+    [i8; 2] => (2, I8),
+    [i8; 4] => (4, I8),
+    u8 => (1, U8),
+    [u8; 2] => (2, U8),
+    [u8; 4] => (4, U8),
+}
+
+pub(crate) struct NativeVertexArray {
+    handle: u32,
+}
+
+impl Drop for NativeVertexArray {
+    fn drop(&mut self) {
+        resource_tracker::unregister(ResourceKind::VertexArray, self.handle);
+        deletion_queue::queue_delete(GlObject::VertexArray(self.handle));
+    }
+}
+
+// Growing a buffer swaps in a new GL object, so attachments remember which
+// one they were last bound to and `bind` catches up with any resize.
+#[derive(Clone)]
+struct Attachment {
+    buffer: Rc<NativeBuffer>,
+    bound_handle: Cell<u32>,
+    stride: i32,
+}
+
+impl Attachment {
+    fn new(buffer: &Rc<NativeBuffer>, stride: i32) -> Attachment {
+        Attachment {
+            buffer: buffer.clone(),
+            bound_handle: Cell::new(buffer.get_handle()),
+            stride,
+        }
+    }
+
+    // The buffer's current handle if it changed since it was last bound.
+    fn take_new_handle(&self) -> Option<u32> {
+        let handle = self.buffer.get_handle();
+
+        if handle == self.bound_handle.replace(handle) {
+            None
+        } else {
+            Some(handle)
+        }
+    }
+}
+
+// Keeps the buffers it references alive.
+#[derive(Clone)]
+pub struct VertexArray {
+    handle: Rc<NativeVertexArray>,
+    vertex_buffers: Vec<Attachment>,
+    index_buffer: Option<Attachment>,
+    index_type: Option<u32>,
+}
+
+impl VertexArray {
+    #[track_caller]
+    pub fn new() -> Result<VertexArray, RlibError> {
+        let mut handle: u32 = 0;
+
+        unsafe {
+            gl::CreateVertexArrays(1, &mut handle);
+        }
+
+        if handle == 0 {
+            return Err(RlibError::GlResource("vertex array".to_string()));
+        }
+
+        resource_tracker::register(
+            ResourceKind::VertexArray,
+            handle,
+            None,
+            0,
+            0,
+            std::panic::Location::caller(),
+        );
+
+        Ok(VertexArray {
+            handle: Rc::new(NativeVertexArray { handle }),
+            vertex_buffers: Vec::new(),
+            index_buffer: None,
+            index_type: None,
+        })
+    }
+
+    pub fn add_vertex_buffer<V: Vertex>(&mut self, buffer: &Buffer<V>) -> u32 {
+        self.add_vertex_buffer_with_layout(buffer, &V::layout())
+    }
+
+    // Returns the binding index the buffer was attached to.
+    pub fn add_vertex_buffer_with_layout<T: Pod>(
+        &mut self,
+        buffer: &Buffer<T>,
+        layout: &VertexLayout,
+    ) -> u32 {
+        let vao = self.handle.handle;
+        let binding = self.vertex_buffers.len() as u32;
+
+        unsafe {
+            gl::VertexArrayVertexBuffer(vao, binding, buffer.get_handle(), 0, layout.stride);
+            gl::VertexArrayBindingDivisor(vao, binding, layout.divisor);
+
+            for attribute in layout.attributes.iter() {
+                gl::EnableVertexArrayAttrib(vao, attribute.location);
+
+                if attribute.attribute_type.is_integer() && !attribute.normalized {
+                    gl::VertexArrayAttribIFormat(
+                        vao,
+                        attribute.location,
+                        attribute.components,
+                        attribute.attribute_type.to_gl(),
+                        attribute.offset,
+                    );
+                } else {
+                    gl::VertexArrayAttribFormat(
+                        vao,
+                        attribute.location,
+                        attribute.components,
+                        attribute.attribute_type.to_gl(),
+                        attribute.normalized as u8,
+                        attribute.offset,
+                    );
+                }
+
+                gl::VertexArrayAttribBinding(vao, attribute.location, binding);
+            }
+        }
+
+        self.vertex_buffers
+            .push(Attachment::new(&buffer.handle, layout.stride));
+
+        binding
+    }
+
+    pub fn set_index_buffer<I: IndexType>(&mut self, buffer: &Buffer<I>) {
+        unsafe {
+            gl::VertexArrayElementBuffer(self.handle.handle, buffer.get_handle());
+        }
+
+        self.index_buffer = Some(Attachment::new(&buffer.handle, 0));
+        self.index_type = Some(I::GL_TYPE);
+        state_cache::forget_element_array_buffer(self.handle.handle);
+    }
+
+    pub fn bind(&self) {
+        let vao = self.handle.handle;
+
+        for (binding, attachment) in self.vertex_buffers.iter().enumerate() {
+            if let Some(handle) = attachment.take_new_handle() {
+                unsafe {
+                    gl::VertexArrayVertexBuffer(vao, binding as u32, handle, 0, attachment.stride);
+                }
+            }
+        }
+
+        if let Some(handle) = self
+            .index_buffer
+            .as_ref()
+            .and_then(Attachment::take_new_handle)
+        {
+            unsafe {
+                gl::VertexArrayElementBuffer(vao, handle);
+            }
+            state_cache::forget_element_array_buffer(vao);
+        }
+
+        state_cache::bind_vertex_array(vao);
+    }
+
+    pub fn unbind(&self) {
+        state_cache::bind_vertex_array(0);
+    }
+
+    pub fn set_label(&self, label: &str) {
+        debug::label_object(gl::VERTEX_ARRAY, self.handle.handle, label);
+        resource_tracker::set_label(ResourceKind::VertexArray, self.handle.handle, label);
+    }
+
+    pub fn get_index_type(&self) -> Option<u32> {
+        self.index_type
+    }
+
+    pub fn get_handle(&self) -> u32 {
+        self.handle.handle
+    }
+}
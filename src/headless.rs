@@ -0,0 +1,86 @@
+use crate::error::RlibError;
+use image::{ImageBuffer, Rgba};
+use sdl2::VideoSubsystem;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeadlessConfig {
+    pub frames: u64,
+    pub capture_path: Option<String>,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> HeadlessConfig {
+        HeadlessConfig {
+            frames: 1,
+            capture_path: None,
+        }
+    }
+}
+
+const OFFSCREEN_DRIVER: &str = "offscreen";
+
+// SDL's offscreen video driver renders into an EGL pbuffer, so this works on
+// GPU-less machines without a display as long as Mesa's llvmpipe is
+// available. The bundled SDL only reads the driver from the environment, so
+// this has to run before `sdl2::init`.
+pub(crate) fn select_offscreen_driver() {
+    std::env::set_var("SDL_VIDEODRIVER", OFFSCREEN_DRIVER);
+}
+
+// SDL doesn't fail over to another driver when the requested one is missing,
+// but a hidden window on a real display must not pass for a headless run.
+pub(crate) fn check_video_driver(video: &VideoSubsystem) -> Result<(), RlibError> {
+    match video.current_video_driver() {
+        OFFSCREEN_DRIVER => Ok(()),
+        driver => Err(unavailable(&format!(
+            "SDL uses the {} video driver instead of {}",
+            driver, OFFSCREEN_DRIVER
+        ))),
+    }
+}
+
+pub(crate) fn unavailable(reason: &str) -> RlibError {
+    RlibError::Sdl(format!("headless mode is unavailable: {}", reason))
+}
+
+pub(crate) fn drain_gl_errors() -> u32 {
+    let mut count = 0;
+
+    loop {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            break;
+        }
+
+        error!("GL error 0x{:04X}", error);
+        count += 1;
+    }
+
+    count
+}
+
+pub(crate) fn capture_framebuffer(width: u32, height: u32, path: &str) -> Result<(), RlibError> {
+    let mut pixels: Vec<u8> = vec![0; (width * height * 4) as usize];
+
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut std::os::raw::c_void,
+        );
+    }
+
+    let image: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_raw(width, height, pixels)
+        .ok_or_else(|| RlibError::GlResource("framebuffer capture".to_string()))?;
+
+    // GL's origin is the bottom-left corner.
+    image::imageops::flip_vertical(&image).save(path)?;
+    Ok(())
+}
@@ -0,0 +1,448 @@
+use super::{ControllerAxis, ControllerButton, Input, Key, MouseButton};
+use crate::error::RlibError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::Path;
+
+// How far an analog axis has to be pushed before it counts as held when an
+// action bound to it is queried as a button.
+const AXIS_BUTTON_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Binding {
+    Key(Key),
+    KeyAxis { negative: Key, positive: Key },
+    MouseButton(MouseButton),
+    ControllerButton(ControllerButton),
+    // Written `axis:-LeftY` when negated, for axes that point the other way
+    // from the keys bound alongside them.
+    ControllerAxis { axis: ControllerAxis, negated: bool },
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "key:{}", key.name()),
+            Binding::KeyAxis { negative, positive } => {
+                write!(f, "keys:{},{}", negative.name(), positive.name())
+            }
+            Binding::MouseButton(button) => write!(f, "mouse:{}", button.name()),
+            Binding::ControllerButton(button) => write!(f, "button:{}", button.name()),
+            Binding::ControllerAxis { axis, negated } => {
+                let sign = if *negated { "-" } else { "" };
+                write!(f, "axis:{}{}", sign, axis.name())
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Binding, String> {
+        let (kind, name) = value
+            .split_once(':')
+            .ok_or_else(|| format!("Binding '{}' is missing a 'kind:' prefix", value))?;
+
+        let binding = match kind {
+            "key" => Key::from_name(name).map(Binding::Key),
+            "keys" => name.split_once(',').and_then(|(negative, positive)| {
+                Some(Binding::KeyAxis {
+                    negative: Key::from_name(negative.trim())?,
+                    positive: Key::from_name(positive.trim())?,
+                })
+            }),
+            "mouse" => MouseButton::from_name(name).map(Binding::MouseButton),
+            "button" => ControllerButton::from_name(name).map(Binding::ControllerButton),
+            "axis" => {
+                let (name, negated) = match name.strip_prefix('-') {
+                    Some(name) => (name, true),
+                    None => (name, false),
+                };
+                ControllerAxis::from_name(name)
+                    .map(|axis| Binding::ControllerAxis { axis, negated })
+            }
+            _ => None,
+        };
+
+        binding.ok_or_else(|| format!("Unknown binding '{}'", value))
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> String {
+        binding.to_string()
+    }
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct ActionMap {
+    actions: BTreeMap<String, Vec<Binding>>,
+}
+
+impl ActionMap {
+    pub fn new() -> ActionMap {
+        ActionMap::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ActionMap, RlibError> {
+        let source = std::fs::read_to_string(path)?;
+        ActionMap::from_toml(&source)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RlibError> {
+        std::fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    pub fn from_toml(source: &str) -> Result<ActionMap, RlibError> {
+        toml::from_str(source).map_err(|e| RlibError::Parse(e.to_string()))
+    }
+
+    pub fn to_toml(&self) -> Result<String, RlibError> {
+        toml::to_string_pretty(self).map_err(|e| RlibError::Parse(e.to_string()))
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    pub fn set_bindings(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.actions.insert(action.to_owned(), bindings);
+    }
+
+    pub fn get_bindings(&self, action: &str) -> &[Binding] {
+        self.actions
+            .get(action)
+            .map_or(&[], |bindings| bindings.as_slice())
+    }
+
+    pub fn get_actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(|action| action.as_str())
+    }
+}
+
+pub struct Actions<'a> {
+    map: &'a ActionMap,
+    input: &'a Input,
+}
+
+impl<'a> Actions<'a> {
+    pub(crate) fn new(map: &'a ActionMap, input: &'a Input) -> Actions<'a> {
+        Actions { map, input }
+    }
+
+    pub fn held(&self, action: &str) -> bool {
+        self.map
+            .get_bindings(action)
+            .iter()
+            .any(|binding| self.binding_value(binding, false).abs() > AXIS_BUTTON_THRESHOLD)
+    }
+
+    pub fn pressed(&self, action: &str) -> bool {
+        self.map
+            .get_bindings(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => self.input.is_key_pressed(*key),
+                Binding::MouseButton(button) => self.input.is_mouse_pressed(*button),
+                Binding::ControllerButton(button) => self
+                    .input
+                    .get_controllers()
+                    .any(|id| self.input.is_controller_button_pressed(id, *button)),
+                _ => {
+                    self.binding_value(binding, false).abs() > AXIS_BUTTON_THRESHOLD
+                        && self.binding_value(binding, true).abs() <= AXIS_BUTTON_THRESHOLD
+                }
+            })
+    }
+
+    pub fn released(&self, action: &str) -> bool {
+        self.map
+            .get_bindings(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => self.input.is_key_released(*key),
+                Binding::MouseButton(button) => self.input.is_mouse_released(*button),
+                Binding::ControllerButton(button) => self
+                    .input
+                    .get_controllers()
+                    .any(|id| self.input.is_controller_button_released(id, *button)),
+                _ => {
+                    self.binding_value(binding, false).abs() <= AXIS_BUTTON_THRESHOLD
+                        && self.binding_value(binding, true).abs() > AXIS_BUTTON_THRESHOLD
+                }
+            })
+    }
+
+    pub fn axis(&self, action: &str) -> f32 {
+        strongest(
+            self.map
+                .get_bindings(action)
+                .iter()
+                .map(|binding| self.binding_value(binding, false)),
+        )
+    }
+
+    fn binding_value(&self, binding: &Binding, previous: bool) -> f32 {
+        let key_value = |key: Key| {
+            let held = self.input.is_key_held(key);
+            // Reconstruct the state at the last tick from the edges since.
+            let was_held = if previous {
+                (held && !self.input.is_key_pressed(key)) || self.input.is_key_released(key)
+            } else {
+                held
+            };
+
+            if was_held {
+                1.0
+            } else {
+                0.0
+            }
+        };
+
+        match binding {
+            Binding::Key(key) => key_value(*key),
+            Binding::KeyAxis { negative, positive } => key_value(*positive) - key_value(*negative),
+            Binding::MouseButton(button) => {
+                if self.input.is_mouse_held(*button) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Binding::ControllerButton(button) => {
+                if self
+                    .input
+                    .get_controllers()
+                    .any(|id| self.input.is_controller_button_held(id, *button))
+                {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Binding::ControllerAxis { axis, negated } => {
+                let value = strongest(self.input.get_controllers().map(|id| {
+                    if previous {
+                        self.input.get_previous_controller_axis(id, *axis)
+                    } else {
+                        self.input.get_controller_axis(id, *axis)
+                    }
+                }));
+
+                if *negated {
+                    -value
+                } else {
+                    value
+                }
+            }
+        }
+    }
+}
+
+// The strongest of all bound inputs wins, so a half-pushed stick doesn't
+// cancel out a held key.
+fn strongest<I: Iterator<Item = f32>>(values: I) -> f32 {
+    values.fold(
+        0.0,
+        |acc, value| if value.abs() > acc.abs() { value } else { acc },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{ControllerId, InputEvent, Modifiers};
+
+    const PAD: ControllerId = ControllerId(0);
+
+    fn key_down(key: Key) -> InputEvent {
+        InputEvent::KeyDown {
+            key,
+            modifiers: Modifiers::default(),
+            repeat: false,
+        }
+    }
+
+    fn key_up(key: Key) -> InputEvent {
+        InputEvent::KeyUp {
+            key,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    fn stick(axis: ControllerAxis, value: f32) -> InputEvent {
+        InputEvent::ControllerAxisMotion {
+            id: PAD,
+            axis,
+            value,
+        }
+    }
+
+    fn input_with(bindings: &[(&str, Binding)]) -> Input {
+        let mut map = ActionMap::new();
+        for (action, binding) in bindings {
+            map.bind(action, *binding);
+        }
+
+        let mut input = Input::new();
+        input.set_action_map(map);
+        input.set_dead_zone(0.2);
+        input.handle_event(&InputEvent::ControllerAdded {
+            id: PAD,
+            name: "Pad".to_string(),
+        });
+        input
+    }
+
+    #[test]
+    fn key_action_edges_follow_ticks() {
+        let mut input = input_with(&[("jump", Binding::Key(Key::Space))]);
+
+        input.handle_event(&key_down(Key::Space));
+        assert!(input.actions().pressed("jump"));
+        assert!(input.actions().held("jump"));
+        assert_eq!(input.actions().axis("jump"), 1.0);
+
+        input.clear_edges();
+        assert!(!input.actions().pressed("jump"));
+        assert!(input.actions().held("jump"));
+
+        input.handle_event(&key_up(Key::Space));
+        assert!(input.actions().released("jump"));
+        assert!(!input.actions().held("jump"));
+
+        assert!(!input.actions().held("unbound"));
+        assert_eq!(input.actions().axis("unbound"), 0.0);
+    }
+
+    #[test]
+    fn any_binding_triggers_the_action() {
+        let mut input = input_with(&[
+            ("jump", Binding::Key(Key::Space)),
+            ("jump", Binding::ControllerButton(ControllerButton::A)),
+            ("jump", Binding::MouseButton(MouseButton::Left)),
+        ]);
+
+        input.handle_event(&InputEvent::ControllerButtonDown {
+            id: PAD,
+            button: ControllerButton::A,
+        });
+        assert!(input.actions().pressed("jump"));
+        input.clear_edges();
+
+        // Still held through the pad, so a second binding adds no press.
+        input.handle_event(&InputEvent::MouseButtonDown {
+            button: MouseButton::Left,
+            x: 0,
+            y: 0,
+            clicks: 1,
+        });
+        input.handle_event(&InputEvent::ControllerButtonUp {
+            id: PAD,
+            button: ControllerButton::A,
+        });
+        assert!(input.actions().held("jump"));
+        assert!(input.actions().released("jump"));
+    }
+
+    #[test]
+    fn strongest_binding_wins_the_axis() {
+        let mut input = input_with(&[
+            (
+                "move_x",
+                Binding::KeyAxis {
+                    negative: Key::A,
+                    positive: Key::D,
+                },
+            ),
+            (
+                "move_x",
+                Binding::ControllerAxis {
+                    axis: ControllerAxis::LeftX,
+                    negated: false,
+                },
+            ),
+        ]);
+
+        input.handle_event(&stick(ControllerAxis::LeftX, 0.6));
+        assert!((input.actions().axis("move_x") - 0.5).abs() < 1e-6);
+
+        input.handle_event(&key_down(Key::A));
+        assert_eq!(input.actions().axis("move_x"), -1.0);
+
+        input.handle_event(&key_down(Key::D));
+        assert!((input.actions().axis("move_x") - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn dead_zone_and_negation_apply_to_axes() {
+        let mut input = input_with(&[(
+            "move_y",
+            Binding::ControllerAxis {
+                axis: ControllerAxis::LeftY,
+                negated: true,
+            },
+        )]);
+
+        input.handle_event(&stick(ControllerAxis::LeftY, 0.15));
+        assert_eq!(input.actions().axis("move_y"), 0.0);
+        assert!(!input.actions().held("move_y"));
+
+        input.handle_event(&stick(ControllerAxis::LeftY, 1.0));
+        assert_eq!(input.actions().axis("move_y"), -1.0);
+        assert!(input.actions().held("move_y"));
+    }
+
+    #[test]
+    fn axes_press_when_crossing_the_threshold() {
+        let mut input = input_with(&[(
+            "fire",
+            Binding::ControllerAxis {
+                axis: ControllerAxis::TriggerRight,
+                negated: false,
+            },
+        )]);
+
+        input.handle_event(&stick(ControllerAxis::TriggerRight, 0.9));
+        assert!(input.actions().pressed("fire"));
+
+        input.clear_edges();
+        assert!(!input.actions().pressed("fire"));
+        assert!(input.actions().held("fire"));
+
+        input.handle_event(&stick(ControllerAxis::TriggerRight, 0.1));
+        assert!(input.actions().released("fire"));
+    }
+
+    #[test]
+    fn negated_axis_round_trips() {
+        let binding = Binding::try_from("axis:-LeftY".to_string()).unwrap();
+        assert_eq!(
+            binding,
+            Binding::ControllerAxis {
+                axis: ControllerAxis::LeftY,
+                negated: true,
+            }
+        );
+        assert_eq!(binding.to_string(), "axis:-LeftY");
+
+        let binding = Binding::try_from("axis:LeftX".to_string()).unwrap();
+        assert_eq!(binding.to_string(), "axis:LeftX");
+
+        assert!(Binding::try_from("axis:-Nope".to_string()).is_err());
+    }
+}
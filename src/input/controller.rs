@@ -0,0 +1,511 @@
+use super::InputEvent;
+use crate::error::RlibError;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ControllerId(pub u32);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ControllerButton {
+    A,
+    B,
+    X,
+    Y,
+    Back,
+    Guide,
+    Start,
+    LeftStick,
+    RightStick,
+    LeftShoulder,
+    RightShoulder,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+pub(crate) const CONTROLLER_BUTTON_COUNT: usize = 15;
+
+impl ControllerButton {
+    pub const ALL: [ControllerButton; CONTROLLER_BUTTON_COUNT] = [
+        ControllerButton::A,
+        ControllerButton::B,
+        ControllerButton::X,
+        ControllerButton::Y,
+        ControllerButton::Back,
+        ControllerButton::Guide,
+        ControllerButton::Start,
+        ControllerButton::LeftStick,
+        ControllerButton::RightStick,
+        ControllerButton::LeftShoulder,
+        ControllerButton::RightShoulder,
+        ControllerButton::DPadUp,
+        ControllerButton::DPadDown,
+        ControllerButton::DPadLeft,
+        ControllerButton::DPadRight,
+    ];
+
+    pub fn from_sdl(button: Button) -> Option<ControllerButton> {
+        match button {
+            Button::A => Some(ControllerButton::A),
+            Button::B => Some(ControllerButton::B),
+            Button::X => Some(ControllerButton::X),
+            Button::Y => Some(ControllerButton::Y),
+            Button::Back => Some(ControllerButton::Back),
+            Button::Guide => Some(ControllerButton::Guide),
+            Button::Start => Some(ControllerButton::Start),
+            Button::LeftStick => Some(ControllerButton::LeftStick),
+            Button::RightStick => Some(ControllerButton::RightStick),
+            Button::LeftShoulder => Some(ControllerButton::LeftShoulder),
+            Button::RightShoulder => Some(ControllerButton::RightShoulder),
+            Button::DPadUp => Some(ControllerButton::DPadUp),
+            Button::DPadDown => Some(ControllerButton::DPadDown),
+            Button::DPadLeft => Some(ControllerButton::DPadLeft),
+            Button::DPadRight => Some(ControllerButton::DPadRight),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControllerButton::A => "A",
+            ControllerButton::B => "B",
+            ControllerButton::X => "X",
+            ControllerButton::Y => "Y",
+            ControllerButton::Back => "Back",
+            ControllerButton::Guide => "Guide",
+            ControllerButton::Start => "Start",
+            ControllerButton::LeftStick => "LeftStick",
+            ControllerButton::RightStick => "RightStick",
+            ControllerButton::LeftShoulder => "LeftShoulder",
+            ControllerButton::RightShoulder => "RightShoulder",
+            ControllerButton::DPadUp => "DPadUp",
+            ControllerButton::DPadDown => "DPadDown",
+            ControllerButton::DPadLeft => "DPadLeft",
+            ControllerButton::DPadRight => "DPadRight",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ControllerButton> {
+        ControllerButton::ALL
+            .iter()
+            .copied()
+            .find(|button| button.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ControllerAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    TriggerLeft,
+    TriggerRight,
+}
+
+pub(crate) const CONTROLLER_AXIS_COUNT: usize = 6;
+
+impl ControllerAxis {
+    pub const ALL: [ControllerAxis; CONTROLLER_AXIS_COUNT] = [
+        ControllerAxis::LeftX,
+        ControllerAxis::LeftY,
+        ControllerAxis::RightX,
+        ControllerAxis::RightY,
+        ControllerAxis::TriggerLeft,
+        ControllerAxis::TriggerRight,
+    ];
+
+    pub fn from_sdl(axis: Axis) -> ControllerAxis {
+        match axis {
+            Axis::LeftX => ControllerAxis::LeftX,
+            Axis::LeftY => ControllerAxis::LeftY,
+            Axis::RightX => ControllerAxis::RightX,
+            Axis::RightY => ControllerAxis::RightY,
+            Axis::TriggerLeft => ControllerAxis::TriggerLeft,
+            Axis::TriggerRight => ControllerAxis::TriggerRight,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ControllerAxis::LeftX => "LeftX",
+            ControllerAxis::LeftY => "LeftY",
+            ControllerAxis::RightX => "RightX",
+            ControllerAxis::RightY => "RightY",
+            ControllerAxis::TriggerLeft => "TriggerLeft",
+            ControllerAxis::TriggerRight => "TriggerRight",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ControllerAxis> {
+        ControllerAxis::ALL
+            .iter()
+            .copied()
+            .find(|axis| axis.name() == name)
+    }
+}
+
+pub(crate) struct ControllerState {
+    pub(crate) name: String,
+    held: [bool; CONTROLLER_BUTTON_COUNT],
+    pressed: [bool; CONTROLLER_BUTTON_COUNT],
+    released: [bool; CONTROLLER_BUTTON_COUNT],
+    axes: [f32; CONTROLLER_AXIS_COUNT],
+    previous_axes: [f32; CONTROLLER_AXIS_COUNT],
+    pub(crate) dead_zone: Option<f32>,
+}
+
+impl ControllerState {
+    pub(crate) fn new(name: String) -> ControllerState {
+        ControllerState {
+            name,
+            held: [false; CONTROLLER_BUTTON_COUNT],
+            pressed: [false; CONTROLLER_BUTTON_COUNT],
+            released: [false; CONTROLLER_BUTTON_COUNT],
+            axes: [0.0; CONTROLLER_AXIS_COUNT],
+            previous_axes: [0.0; CONTROLLER_AXIS_COUNT],
+            dead_zone: None,
+        }
+    }
+
+    pub(crate) fn clear_edges(&mut self) {
+        self.pressed = [false; CONTROLLER_BUTTON_COUNT];
+        self.released = [false; CONTROLLER_BUTTON_COUNT];
+        self.previous_axes = self.axes;
+    }
+
+    pub(crate) fn button_down(&mut self, button: ControllerButton) {
+        if !self.held[button as usize] {
+            self.pressed[button as usize] = true;
+        }
+        self.held[button as usize] = true;
+    }
+
+    pub(crate) fn button_up(&mut self, button: ControllerButton) {
+        if self.held[button as usize] {
+            self.released[button as usize] = true;
+        }
+        self.held[button as usize] = false;
+    }
+
+    pub(crate) fn set_axis(&mut self, axis: ControllerAxis, value: f32) {
+        self.axes[axis as usize] = value;
+    }
+
+    pub(crate) fn is_held(&self, button: ControllerButton) -> bool {
+        self.held[button as usize]
+    }
+
+    pub(crate) fn is_pressed(&self, button: ControllerButton) -> bool {
+        self.pressed[button as usize]
+    }
+
+    pub(crate) fn is_released(&self, button: ControllerButton) -> bool {
+        self.released[button as usize]
+    }
+
+    pub(crate) fn get_axis(&self, axis: ControllerAxis, dead_zone: f32) -> f32 {
+        self.apply_dead_zone(self.axes[axis as usize], dead_zone)
+    }
+
+    pub(crate) fn get_previous_axis(&self, axis: ControllerAxis, dead_zone: f32) -> f32 {
+        self.apply_dead_zone(self.previous_axes[axis as usize], dead_zone)
+    }
+
+    fn apply_dead_zone(&self, value: f32, dead_zone: f32) -> f32 {
+        let dead_zone = self.dead_zone.unwrap_or(dead_zone).clamp(0.0, 0.99);
+
+        if value.abs() <= dead_zone {
+            0.0
+        } else {
+            value.signum() * (value.abs() - dead_zone) / (1.0 - dead_zone)
+        }
+    }
+}
+
+// SDL identifies a connected controller by its joystick instance id, which
+// changes on every reconnect. We hand out the lowest free slot instead so a
+// replugged pad keeps its player number.
+pub struct Controllers {
+    subsystem: Option<GameControllerSubsystem>,
+    connected: HashMap<u32, (ControllerId, Option<GameController>)>,
+    slots: Vec<bool>,
+}
+
+impl Controllers {
+    pub fn new(subsystem: Option<GameControllerSubsystem>) -> Controllers {
+        Controllers {
+            subsystem,
+            connected: HashMap::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    pub fn load_mappings<P: AsRef<Path>>(&self, path: P) -> Result<i32, RlibError> {
+        match &self.subsystem {
+            Some(subsystem) => subsystem
+                .load_mappings(path)
+                .map_err(|e| RlibError::Sdl(e.to_string())),
+            None => Err(RlibError::Sdl(
+                "Game controller subsystem is not initialized".to_string(),
+            )),
+        }
+    }
+
+    // Without a subsystem (as when feeding synthetic events) the `which` of a
+    // device-added event is taken to be the instance id directly.
+    pub fn translate(&mut self, event: &Event) -> Option<InputEvent> {
+        match event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let (instance_id, name, controller) = match &self.subsystem {
+                    Some(subsystem) => match subsystem.open(*which) {
+                        Ok(controller) => (
+                            controller.instance_id(),
+                            controller.name(),
+                            Some(controller),
+                        ),
+                        Err(e) => {
+                            error!("Failed to open game controller {}: {}", which, e);
+                            return None;
+                        }
+                    },
+                    None => (*which, format!("Controller {}", which), None),
+                };
+
+                if self.connected.contains_key(&instance_id) {
+                    return None;
+                }
+
+                let id = self.allocate_slot();
+                info!("Game controller connected: {} ({:?})", name, id);
+                self.connected.insert(instance_id, (id, controller));
+
+                Some(InputEvent::ControllerAdded { id, name })
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                let (id, _) = self.connected.remove(which)?;
+                self.slots[id.0 as usize] = false;
+                info!("Game controller disconnected: {:?}", id);
+
+                Some(InputEvent::ControllerRemoved { id })
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                Some(InputEvent::ControllerButtonDown {
+                    id: self.connected.get(which)?.0,
+                    button: ControllerButton::from_sdl(*button)?,
+                })
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                Some(InputEvent::ControllerButtonUp {
+                    id: self.connected.get(which)?.0,
+                    button: ControllerButton::from_sdl(*button)?,
+                })
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => Some(InputEvent::ControllerAxisMotion {
+                id: self.connected.get(which)?.0,
+                axis: ControllerAxis::from_sdl(*axis),
+                value: (*value as f32 / i16::MAX as f32).clamp(-1.0, 1.0),
+            }),
+            _ => None,
+        }
+    }
+
+    fn allocate_slot(&mut self) -> ControllerId {
+        let slot = match self.slots.iter().position(|used| !used) {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(false);
+                self.slots.len() - 1
+            }
+        };
+
+        self.slots[slot] = true;
+        ControllerId(slot as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Input;
+
+    fn added(which: u32) -> Event {
+        Event::ControllerDeviceAdded {
+            timestamp: 0,
+            which,
+        }
+    }
+
+    fn removed(which: u32) -> Event {
+        Event::ControllerDeviceRemoved {
+            timestamp: 0,
+            which,
+        }
+    }
+
+    fn axis_motion(which: u32, axis: Axis, value: i16) -> Event {
+        Event::ControllerAxisMotion {
+            timestamp: 0,
+            which,
+            axis,
+            value,
+        }
+    }
+
+    fn feed(controllers: &mut Controllers, input: &mut Input, event: Event) -> Option<InputEvent> {
+        let input_event = controllers.translate(&event);
+
+        if let Some(input_event) = &input_event {
+            input.handle_event(input_event);
+        }
+
+        input_event
+    }
+
+    fn get_added_id(event: Option<InputEvent>) -> ControllerId {
+        match event {
+            Some(InputEvent::ControllerAdded { id, .. }) => id,
+            event => panic!("expected ControllerAdded, got {:?}", event),
+        }
+    }
+
+    #[test]
+    fn reconnected_controller_reuses_its_slot() {
+        let mut controllers = Controllers::new(None);
+        let mut input = Input::new();
+
+        let first = get_added_id(feed(&mut controllers, &mut input, added(10)));
+        let second = get_added_id(feed(&mut controllers, &mut input, added(11)));
+        assert_eq!(first, ControllerId(0));
+        assert_eq!(second, ControllerId(1));
+
+        assert_eq!(
+            feed(&mut controllers, &mut input, removed(10)),
+            Some(InputEvent::ControllerRemoved { id: first })
+        );
+        assert!(!input.is_controller_connected(first));
+        assert!(input.is_controller_connected(second));
+
+        // SDL hands the replugged pad a new instance id.
+        let reconnected = get_added_id(feed(&mut controllers, &mut input, added(12)));
+        assert_eq!(reconnected, first);
+        assert!(input.is_controller_connected(reconnected));
+    }
+
+    #[test]
+    fn duplicate_add_and_unknown_remove_are_ignored() {
+        let mut controllers = Controllers::new(None);
+        let mut input = Input::new();
+
+        get_added_id(feed(&mut controllers, &mut input, added(3)));
+        assert_eq!(feed(&mut controllers, &mut input, added(3)), None);
+        assert_eq!(feed(&mut controllers, &mut input, removed(4)), None);
+        assert_eq!(input.get_controllers().count(), 1);
+    }
+
+    #[test]
+    fn axes_are_normalized_and_dead_zoned() {
+        let mut controllers = Controllers::new(None);
+        let mut input = Input::new();
+        input.set_dead_zone(0.2);
+
+        let id = get_added_id(feed(&mut controllers, &mut input, added(0)));
+
+        feed(
+            &mut controllers,
+            &mut input,
+            axis_motion(0, Axis::LeftX, i16::MIN),
+        );
+        assert_eq!(input.get_controller_axis(id, ControllerAxis::LeftX), -1.0);
+
+        feed(
+            &mut controllers,
+            &mut input,
+            axis_motion(0, Axis::LeftX, i16::MAX),
+        );
+        assert_eq!(input.get_controller_axis(id, ControllerAxis::LeftX), 1.0);
+
+        feed(
+            &mut controllers,
+            &mut input,
+            axis_motion(0, Axis::LeftX, 6000),
+        );
+        assert_eq!(input.get_controller_axis(id, ControllerAxis::LeftX), 0.0);
+
+        // Halfway between the dead zone and the edge.
+        feed(
+            &mut controllers,
+            &mut input,
+            axis_motion(0, Axis::LeftY, (0.6 * i16::MAX as f32) as i16),
+        );
+        let value = input.get_controller_axis(id, ControllerAxis::LeftY);
+        assert!((value - 0.5).abs() < 1e-3, "got {}", value);
+
+        input.set_controller_dead_zone(id, Some(0.0));
+        feed(
+            &mut controllers,
+            &mut input,
+            axis_motion(0, Axis::LeftX, 6000),
+        );
+        assert!(input.get_controller_axis(id, ControllerAxis::LeftX) > 0.0);
+    }
+
+    #[test]
+    fn button_edges_last_until_cleared() {
+        let mut controllers = Controllers::new(None);
+        let mut input = Input::new();
+        let id = get_added_id(feed(&mut controllers, &mut input, added(0)));
+
+        let down = Event::ControllerButtonDown {
+            timestamp: 0,
+            which: 0,
+            button: Button::A,
+        };
+        let up = Event::ControllerButtonUp {
+            timestamp: 0,
+            which: 0,
+            button: Button::A,
+        };
+
+        feed(&mut controllers, &mut input, down.clone());
+        assert!(input.is_controller_button_pressed(id, ControllerButton::A));
+        assert!(input.is_controller_button_held(id, ControllerButton::A));
+
+        input.clear_edges();
+        assert!(!input.is_controller_button_pressed(id, ControllerButton::A));
+        assert!(input.is_controller_button_held(id, ControllerButton::A));
+
+        // A repeated down while held is not a new press.
+        feed(&mut controllers, &mut input, down);
+        assert!(!input.is_controller_button_pressed(id, ControllerButton::A));
+
+        feed(&mut controllers, &mut input, up);
+        assert!(input.is_controller_button_released(id, ControllerButton::A));
+        assert!(!input.is_controller_button_held(id, ControllerButton::A));
+
+        input.clear_edges();
+        assert!(!input.is_controller_button_released(id, ControllerButton::A));
+    }
+
+    #[test]
+    fn events_for_unknown_controllers_are_dropped() {
+        let mut controllers = Controllers::new(None);
+        let mut input = Input::new();
+
+        assert_eq!(
+            feed(
+                &mut controllers,
+                &mut input,
+                axis_motion(7, Axis::LeftX, 100)
+            ),
+            None
+        );
+    }
+}
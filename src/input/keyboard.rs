@@ -0,0 +1,186 @@
+use sdl2::keyboard::{Mod, Scancode};
+
+macro_rules! keys {
+    ($($key:ident),* $(,)?) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum Key {
+            $($key),*
+        }
+
+        impl Key {
+            pub const ALL: &'static [Key] = &[$(Key::$key),*];
+
+            pub fn from_scancode(scancode: Scancode) -> Option<Key> {
+                match scancode {
+                    $(Scancode::$key => Some(Key::$key),)*
+                    _ => None,
+                }
+            }
+
+            pub fn to_scancode(self) -> Scancode {
+                match self {
+                    $(Key::$key => Scancode::$key),*
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Key::$key => stringify!($key)),*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Key> {
+                match name {
+                    $(stringify!($key) => Some(Key::$key),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+keys! {
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0,
+    Return, Escape, Backspace, Tab, Space,
+    Minus, Equals, LeftBracket, RightBracket, Backslash, Semicolon, Apostrophe, Grave,
+    Comma, Period, Slash, CapsLock,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    PrintScreen, ScrollLock, Pause, Insert, Home, PageUp, Delete, End, PageDown,
+    Right, Left, Down, Up,
+    NumLockClear, KpDivide, KpMultiply, KpMinus, KpPlus, KpEnter,
+    Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9, Kp0, KpPeriod,
+    LCtrl, LShift, LAlt, LGui, RCtrl, RShift, RAlt, RGui,
+}
+
+pub(crate) const KEY_COUNT: usize = Key::ALL.len();
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub gui: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+}
+
+impl Modifiers {
+    pub fn from_sdl(keymod: Mod) -> Modifiers {
+        Modifiers {
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+            gui: keymod.intersects(Mod::LGUIMOD | Mod::RGUIMOD),
+            caps_lock: keymod.contains(Mod::CAPSMOD),
+            num_lock: keymod.contains(Mod::NUMMOD),
+        }
+    }
+}
+
+pub(crate) struct KeyboardState {
+    held: [bool; KEY_COUNT],
+    pressed: [bool; KEY_COUNT],
+    released: [bool; KEY_COUNT],
+    pub(crate) modifiers: Modifiers,
+}
+
+impl KeyboardState {
+    pub(crate) fn new() -> KeyboardState {
+        KeyboardState {
+            held: [false; KEY_COUNT],
+            pressed: [false; KEY_COUNT],
+            released: [false; KEY_COUNT],
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    pub(crate) fn clear_edges(&mut self) {
+        self.pressed = [false; KEY_COUNT];
+        self.released = [false; KEY_COUNT];
+    }
+
+    pub(crate) fn key_down(&mut self, key: Key) {
+        if !self.held[key as usize] {
+            self.pressed[key as usize] = true;
+        }
+        self.held[key as usize] = true;
+    }
+
+    pub(crate) fn key_up(&mut self, key: Key) {
+        if self.held[key as usize] {
+            self.released[key as usize] = true;
+        }
+        self.held[key as usize] = false;
+    }
+
+    pub(crate) fn is_held(&self, key: Key) -> bool {
+        self.held[key as usize]
+    }
+
+    pub(crate) fn is_pressed(&self, key: Key) -> bool {
+        self.pressed[key as usize]
+    }
+
+    pub(crate) fn is_released(&self, key: Key) -> bool {
+        self.released[key as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn press_lasts_until_edges_are_cleared() {
+        let mut keyboard = KeyboardState::new();
+        keyboard.key_down(Key::A);
+        assert!(keyboard.is_pressed(Key::A));
+        assert!(keyboard.is_held(Key::A));
+
+        keyboard.clear_edges();
+        assert!(!keyboard.is_pressed(Key::A));
+        assert!(keyboard.is_held(Key::A));
+
+        keyboard.key_up(Key::A);
+        assert!(keyboard.is_released(Key::A));
+        assert!(!keyboard.is_held(Key::A));
+
+        keyboard.clear_edges();
+        assert!(!keyboard.is_released(Key::A));
+    }
+
+    #[test]
+    fn tap_within_one_tick_keeps_both_edges() {
+        let mut keyboard = KeyboardState::new();
+        keyboard.key_down(Key::Space);
+        keyboard.key_up(Key::Space);
+
+        assert!(keyboard.is_pressed(Key::Space));
+        assert!(keyboard.is_released(Key::Space));
+        assert!(!keyboard.is_held(Key::Space));
+    }
+
+    #[test]
+    fn repeats_and_stray_releases_add_no_edges() {
+        let mut keyboard = KeyboardState::new();
+        keyboard.key_down(Key::W);
+        keyboard.clear_edges();
+
+        keyboard.key_down(Key::W);
+        assert!(!keyboard.is_pressed(Key::W));
+        assert!(keyboard.is_held(Key::W));
+
+        keyboard.key_up(Key::S);
+        assert!(!keyboard.is_released(Key::S));
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for key in Key::ALL {
+            assert_eq!(Key::from_name(key.name()), Some(*key));
+            assert_eq!(Key::from_scancode(key.to_scancode()), Some(*key));
+        }
+        assert_eq!(Key::from_name("Nope"), None);
+    }
+}
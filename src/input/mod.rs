@@ -0,0 +1,478 @@
+pub mod actions;
+pub mod controller;
+pub mod keyboard;
+pub mod mouse;
+pub mod recording;
+
+pub use actions::{ActionMap, Actions, Binding};
+pub use controller::{ControllerAxis, ControllerButton, ControllerId, Controllers};
+pub use keyboard::{Key, Modifiers};
+pub use mouse::MouseButton;
+
+use controller::ControllerState;
+use keyboard::KeyboardState;
+use mouse::MouseState;
+use sdl2::event::Event;
+use sdl2::mouse::MouseWheelDirection;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputEvent {
+    KeyDown {
+        key: Key,
+        modifiers: Modifiers,
+        repeat: bool,
+    },
+    KeyUp {
+        key: Key,
+        modifiers: Modifiers,
+    },
+    MouseButtonDown {
+        button: MouseButton,
+        x: i32,
+        y: i32,
+        clicks: u8,
+    },
+    MouseButtonUp {
+        button: MouseButton,
+        x: i32,
+        y: i32,
+    },
+    MouseMotion {
+        x: i32,
+        y: i32,
+        dx: i32,
+        dy: i32,
+    },
+    MouseWheel {
+        x: i32,
+        y: i32,
+    },
+    TextInput {
+        text: String,
+    },
+    ControllerAdded {
+        id: ControllerId,
+        name: String,
+    },
+    ControllerRemoved {
+        id: ControllerId,
+    },
+    ControllerButtonDown {
+        id: ControllerId,
+        button: ControllerButton,
+    },
+    ControllerButtonUp {
+        id: ControllerId,
+        button: ControllerButton,
+    },
+    ControllerAxisMotion {
+        id: ControllerId,
+        axis: ControllerAxis,
+        value: f32,
+    },
+}
+
+impl InputEvent {
+    pub fn from_sdl(event: &Event) -> Option<InputEvent> {
+        match event {
+            Event::KeyDown {
+                scancode: Some(scancode),
+                keymod,
+                repeat,
+                ..
+            } => Some(InputEvent::KeyDown {
+                key: Key::from_scancode(*scancode)?,
+                modifiers: Modifiers::from_sdl(*keymod),
+                repeat: *repeat,
+            }),
+            Event::KeyUp {
+                scancode: Some(scancode),
+                keymod,
+                ..
+            } => Some(InputEvent::KeyUp {
+                key: Key::from_scancode(*scancode)?,
+                modifiers: Modifiers::from_sdl(*keymod),
+            }),
+            Event::MouseButtonDown {
+                mouse_btn,
+                clicks,
+                x,
+                y,
+                ..
+            } => Some(InputEvent::MouseButtonDown {
+                button: MouseButton::from_sdl(*mouse_btn)?,
+                x: *x,
+                y: *y,
+                clicks: *clicks,
+            }),
+            Event::MouseButtonUp {
+                mouse_btn, x, y, ..
+            } => Some(InputEvent::MouseButtonUp {
+                button: MouseButton::from_sdl(*mouse_btn)?,
+                x: *x,
+                y: *y,
+            }),
+            Event::MouseMotion {
+                x, y, xrel, yrel, ..
+            } => Some(InputEvent::MouseMotion {
+                x: *x,
+                y: *y,
+                dx: *xrel,
+                dy: *yrel,
+            }),
+            Event::MouseWheel {
+                x, y, direction, ..
+            } => {
+                let sign = match direction {
+                    MouseWheelDirection::Flipped => -1,
+                    _ => 1,
+                };
+
+                Some(InputEvent::MouseWheel {
+                    x: *x * sign,
+                    y: *y * sign,
+                })
+            }
+            Event::TextInput { text, .. } => Some(InputEvent::TextInput { text: text.clone() }),
+            _ => None,
+        }
+    }
+}
+
+pub struct Input {
+    keyboard: KeyboardState,
+    mouse: MouseState,
+    controllers: Vec<Option<ControllerState>>,
+    dead_zone: f32,
+    text: String,
+    action_map: ActionMap,
+}
+
+impl Default for Input {
+    fn default() -> Input {
+        Input::new()
+    }
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input {
+            keyboard: KeyboardState::new(),
+            mouse: MouseState::new(),
+            controllers: Vec::new(),
+            dead_zone: 0.15,
+            text: String::new(),
+            action_map: ActionMap::new(),
+        }
+    }
+
+    // Pressed and released edges, deltas and text accumulate across frames
+    // until an update has seen them, so a tap lands in exactly one tick at
+    // any frame rate.
+    pub(crate) fn clear_edges(&mut self) {
+        self.keyboard.clear_edges();
+        self.mouse.clear_edges();
+        self.text.clear();
+
+        for controller in self.controllers.iter_mut().flatten() {
+            controller.clear_edges();
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &InputEvent) {
+        match event {
+            InputEvent::KeyDown { key, modifiers, .. } => {
+                self.keyboard.key_down(*key);
+                self.keyboard.modifiers = *modifiers;
+            }
+            InputEvent::KeyUp { key, modifiers } => {
+                self.keyboard.key_up(*key);
+                self.keyboard.modifiers = *modifiers;
+            }
+            InputEvent::MouseButtonDown { button, x, y, .. } => {
+                self.mouse.button_down(*button);
+                self.mouse.x = *x;
+                self.mouse.y = *y;
+            }
+            InputEvent::MouseButtonUp { button, x, y } => {
+                self.mouse.button_up(*button);
+                self.mouse.x = *x;
+                self.mouse.y = *y;
+            }
+            InputEvent::MouseMotion { x, y, dx, dy } => {
+                self.mouse.x = *x;
+                self.mouse.y = *y;
+                self.mouse.dx += *dx;
+                self.mouse.dy += *dy;
+            }
+            InputEvent::MouseWheel { x, y } => {
+                self.mouse.wheel_x += *x;
+                self.mouse.wheel_y += *y;
+            }
+            InputEvent::TextInput { text } => {
+                self.text.push_str(text);
+            }
+            InputEvent::ControllerAdded { id, name } => {
+                let slot = id.0 as usize;
+                if self.controllers.len() <= slot {
+                    self.controllers.resize_with(slot + 1, || None);
+                }
+                self.controllers[slot] = Some(ControllerState::new(name.clone()));
+            }
+            InputEvent::ControllerRemoved { id } => {
+                if let Some(controller) = self.controllers.get_mut(id.0 as usize) {
+                    *controller = None;
+                }
+            }
+            InputEvent::ControllerButtonDown { id, button } => {
+                if let Some(controller) = self.get_controller_mut(*id) {
+                    controller.button_down(*button);
+                }
+            }
+            InputEvent::ControllerButtonUp { id, button } => {
+                if let Some(controller) = self.get_controller_mut(*id) {
+                    controller.button_up(*button);
+                }
+            }
+            InputEvent::ControllerAxisMotion { id, axis, value } => {
+                if let Some(controller) = self.get_controller_mut(*id) {
+                    controller.set_axis(*axis, *value);
+                }
+            }
+        }
+    }
+
+    fn get_controller(&self, id: ControllerId) -> Option<&ControllerState> {
+        self.controllers.get(id.0 as usize)?.as_ref()
+    }
+
+    fn get_controller_mut(&mut self, id: ControllerId) -> Option<&mut ControllerState> {
+        self.controllers.get_mut(id.0 as usize)?.as_mut()
+    }
+
+    pub fn actions(&self) -> Actions<'_> {
+        Actions::new(&self.action_map, self)
+    }
+
+    pub fn get_action_map(&self) -> &ActionMap {
+        &self.action_map
+    }
+
+    pub fn get_action_map_mut(&mut self) -> &mut ActionMap {
+        &mut self.action_map
+    }
+
+    pub fn set_action_map(&mut self, action_map: ActionMap) {
+        self.action_map = action_map;
+    }
+
+    pub fn is_key_held(&self, key: Key) -> bool {
+        self.keyboard.is_held(key)
+    }
+
+    pub fn is_key_pressed(&self, key: Key) -> bool {
+        self.keyboard.is_pressed(key)
+    }
+
+    pub fn is_key_released(&self, key: Key) -> bool {
+        self.keyboard.is_released(key)
+    }
+
+    pub fn get_modifiers(&self) -> Modifiers {
+        self.keyboard.modifiers
+    }
+
+    pub fn is_mouse_held(&self, button: MouseButton) -> bool {
+        self.mouse.is_held(button)
+    }
+
+    pub fn is_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.mouse.is_pressed(button)
+    }
+
+    pub fn is_mouse_released(&self, button: MouseButton) -> bool {
+        self.mouse.is_released(button)
+    }
+
+    pub fn get_mouse_position(&self) -> (i32, i32) {
+        (self.mouse.x, self.mouse.y)
+    }
+
+    pub fn get_mouse_delta(&self) -> (i32, i32) {
+        (self.mouse.dx, self.mouse.dy)
+    }
+
+    pub fn get_wheel_delta(&self) -> (i32, i32) {
+        (self.mouse.wheel_x, self.mouse.wheel_y)
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn get_controllers(&self) -> impl Iterator<Item = ControllerId> + '_ {
+        self.controllers
+            .iter()
+            .enumerate()
+            .filter(|(_, controller)| controller.is_some())
+            .map(|(slot, _)| ControllerId(slot as u32))
+    }
+
+    pub fn is_controller_connected(&self, id: ControllerId) -> bool {
+        self.get_controller(id).is_some()
+    }
+
+    pub fn get_controller_name(&self, id: ControllerId) -> Option<&str> {
+        self.get_controller(id)
+            .map(|controller| controller.name.as_str())
+    }
+
+    pub fn is_controller_button_held(&self, id: ControllerId, button: ControllerButton) -> bool {
+        self.get_controller(id)
+            .is_some_and(|controller| controller.is_held(button))
+    }
+
+    pub fn is_controller_button_pressed(&self, id: ControllerId, button: ControllerButton) -> bool {
+        self.get_controller(id)
+            .is_some_and(|controller| controller.is_pressed(button))
+    }
+
+    pub fn is_controller_button_released(
+        &self,
+        id: ControllerId,
+        button: ControllerButton,
+    ) -> bool {
+        self.get_controller(id)
+            .is_some_and(|controller| controller.is_released(button))
+    }
+
+    pub fn get_controller_axis(&self, id: ControllerId, axis: ControllerAxis) -> f32 {
+        self.get_controller(id)
+            .map_or(0.0, |controller| controller.get_axis(axis, self.dead_zone))
+    }
+
+    pub(crate) fn get_previous_controller_axis(
+        &self,
+        id: ControllerId,
+        axis: ControllerAxis,
+    ) -> f32 {
+        self.get_controller(id).map_or(0.0, |controller| {
+            controller.get_previous_axis(axis, self.dead_zone)
+        })
+    }
+
+    pub fn get_dead_zone(&self) -> f32 {
+        self.dead_zone
+    }
+
+    pub fn set_dead_zone(&mut self, dead_zone: f32) {
+        self.dead_zone = dead_zone;
+    }
+
+    pub fn set_controller_dead_zone(&mut self, id: ControllerId, dead_zone: Option<f32>) {
+        if let Some(controller) = self.get_controller_mut(id) {
+            controller.dead_zone = dead_zone;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_down(key: Key, repeat: bool) -> InputEvent {
+        InputEvent::KeyDown {
+            key,
+            modifiers: Modifiers::default(),
+            repeat,
+        }
+    }
+
+    fn key_up(key: Key) -> InputEvent {
+        InputEvent::KeyUp {
+            key,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    #[test]
+    fn press_survives_frames_until_a_tick_clears_it() {
+        let mut input = Input::new();
+
+        // Two rendered frames without a tick in between.
+        input.handle_event(&key_down(Key::A, false));
+        input.handle_event(&key_down(Key::A, true));
+        assert!(input.is_key_pressed(Key::A));
+
+        input.clear_edges();
+        assert!(!input.is_key_pressed(Key::A));
+        assert!(input.is_key_held(Key::A));
+
+        input.handle_event(&key_down(Key::A, true));
+        assert!(!input.is_key_pressed(Key::A));
+    }
+
+    #[test]
+    fn press_and_release_within_one_tick_are_both_seen() {
+        let mut input = Input::new();
+        input.handle_event(&key_down(Key::Space, false));
+        input.handle_event(&key_up(Key::Space));
+
+        assert!(input.is_key_pressed(Key::Space));
+        assert!(input.is_key_released(Key::Space));
+        assert!(!input.is_key_held(Key::Space));
+
+        input.clear_edges();
+        assert!(!input.is_key_pressed(Key::Space));
+        assert!(!input.is_key_released(Key::Space));
+    }
+
+    #[test]
+    fn mouse_deltas_wheel_and_text_accumulate_until_cleared() {
+        let mut input = Input::new();
+        for (x, dx) in [(5, 5), (12, 7)] {
+            input.handle_event(&InputEvent::MouseMotion {
+                x,
+                y: 0,
+                dx,
+                dy: -1,
+            });
+        }
+        input.handle_event(&InputEvent::MouseWheel { x: 0, y: 1 });
+        input.handle_event(&InputEvent::MouseWheel { x: 1, y: 2 });
+        input.handle_event(&InputEvent::TextInput {
+            text: "a".to_string(),
+        });
+        input.handle_event(&InputEvent::TextInput {
+            text: "b".to_string(),
+        });
+
+        assert_eq!(input.get_mouse_position(), (12, 0));
+        assert_eq!(input.get_mouse_delta(), (12, -2));
+        assert_eq!(input.get_wheel_delta(), (1, 3));
+        assert_eq!(input.get_text(), "ab");
+
+        input.clear_edges();
+        assert_eq!(input.get_mouse_position(), (12, 0));
+        assert_eq!(input.get_mouse_delta(), (0, 0));
+        assert_eq!(input.get_wheel_delta(), (0, 0));
+        assert_eq!(input.get_text(), "");
+    }
+
+    #[test]
+    fn flipped_wheel_events_are_normalized() {
+        let event = Event::MouseWheel {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            x: 1,
+            y: 2,
+            direction: MouseWheelDirection::Flipped,
+        };
+
+        assert_eq!(
+            InputEvent::from_sdl(&event),
+            Some(InputEvent::MouseWheel { x: -1, y: -2 })
+        );
+    }
+}
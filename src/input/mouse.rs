@@ -0,0 +1,162 @@
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    X1,
+    X2,
+}
+
+pub(crate) const MOUSE_BUTTON_COUNT: usize = 5;
+
+impl MouseButton {
+    pub const ALL: [MouseButton; MOUSE_BUTTON_COUNT] = [
+        MouseButton::Left,
+        MouseButton::Middle,
+        MouseButton::Right,
+        MouseButton::X1,
+        MouseButton::X2,
+    ];
+
+    pub fn from_sdl(button: sdl2::mouse::MouseButton) -> Option<MouseButton> {
+        match button {
+            sdl2::mouse::MouseButton::Left => Some(MouseButton::Left),
+            sdl2::mouse::MouseButton::Middle => Some(MouseButton::Middle),
+            sdl2::mouse::MouseButton::Right => Some(MouseButton::Right),
+            sdl2::mouse::MouseButton::X1 => Some(MouseButton::X1),
+            sdl2::mouse::MouseButton::X2 => Some(MouseButton::X2),
+            sdl2::mouse::MouseButton::Unknown => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MouseButton::Left => "Left",
+            MouseButton::Middle => "Middle",
+            MouseButton::Right => "Right",
+            MouseButton::X1 => "X1",
+            MouseButton::X2 => "X2",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MouseButton> {
+        MouseButton::ALL
+            .iter()
+            .copied()
+            .find(|button| button.name() == name)
+    }
+}
+
+pub(crate) struct MouseState {
+    held: [bool; MOUSE_BUTTON_COUNT],
+    pressed: [bool; MOUSE_BUTTON_COUNT],
+    released: [bool; MOUSE_BUTTON_COUNT],
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) dx: i32,
+    pub(crate) dy: i32,
+    pub(crate) wheel_x: i32,
+    pub(crate) wheel_y: i32,
+}
+
+impl MouseState {
+    pub(crate) fn new() -> MouseState {
+        MouseState {
+            held: [false; MOUSE_BUTTON_COUNT],
+            pressed: [false; MOUSE_BUTTON_COUNT],
+            released: [false; MOUSE_BUTTON_COUNT],
+            x: 0,
+            y: 0,
+            dx: 0,
+            dy: 0,
+            wheel_x: 0,
+            wheel_y: 0,
+        }
+    }
+
+    pub(crate) fn clear_edges(&mut self) {
+        self.pressed = [false; MOUSE_BUTTON_COUNT];
+        self.released = [false; MOUSE_BUTTON_COUNT];
+        self.dx = 0;
+        self.dy = 0;
+        self.wheel_x = 0;
+        self.wheel_y = 0;
+    }
+
+    pub(crate) fn button_down(&mut self, button: MouseButton) {
+        if !self.held[button as usize] {
+            self.pressed[button as usize] = true;
+        }
+        self.held[button as usize] = true;
+    }
+
+    pub(crate) fn button_up(&mut self, button: MouseButton) {
+        if self.held[button as usize] {
+            self.released[button as usize] = true;
+        }
+        self.held[button as usize] = false;
+    }
+
+    pub(crate) fn is_held(&self, button: MouseButton) -> bool {
+        self.held[button as usize]
+    }
+
+    pub(crate) fn is_pressed(&self, button: MouseButton) -> bool {
+        self.pressed[button as usize]
+    }
+
+    pub(crate) fn is_released(&self, button: MouseButton) -> bool {
+        self.released[button as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn button_edges_last_until_cleared() {
+        let mut mouse = MouseState::new();
+        mouse.button_down(MouseButton::Left);
+        mouse.button_up(MouseButton::Left);
+        mouse.button_down(MouseButton::Right);
+
+        assert!(mouse.is_pressed(MouseButton::Left));
+        assert!(mouse.is_released(MouseButton::Left));
+        assert!(!mouse.is_held(MouseButton::Left));
+        assert!(mouse.is_held(MouseButton::Right));
+
+        mouse.clear_edges();
+        assert!(!mouse.is_pressed(MouseButton::Left));
+        assert!(!mouse.is_released(MouseButton::Left));
+        assert!(!mouse.is_pressed(MouseButton::Right));
+        assert!(mouse.is_held(MouseButton::Right));
+
+        mouse.button_down(MouseButton::Right);
+        assert!(!mouse.is_pressed(MouseButton::Right));
+    }
+
+    #[test]
+    fn clearing_resets_deltas_but_not_the_position() {
+        let mut mouse = MouseState::new();
+        mouse.x = 10;
+        mouse.y = 20;
+        mouse.dx = 3;
+        mouse.wheel_y = -2;
+
+        mouse.clear_edges();
+        assert_eq!((mouse.x, mouse.y), (10, 20));
+        assert_eq!(
+            (mouse.dx, mouse.dy, mouse.wheel_x, mouse.wheel_y),
+            (0, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for button in MouseButton::ALL {
+            assert_eq!(MouseButton::from_name(button.name()), Some(button));
+        }
+        assert_eq!(MouseButton::from_name("Nope"), None);
+    }
+}
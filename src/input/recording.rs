@@ -0,0 +1,615 @@
+use super::{
+    ControllerAxis, ControllerButton, ControllerId, InputEvent, Key, Modifiers, MouseButton,
+};
+use crate::error::RlibError;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const MAGIC: &[u8; 4] = b"RLIR";
+const VERSION: u8 = 1;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RecordedFrame {
+    pub index: u64,
+    pub timestamp: Duration,
+    pub frame_time: Duration,
+    pub events: Vec<InputEvent>,
+}
+
+// Every frame is flushed as soon as it's recorded so a crash still leaves a
+// usable recording behind. Everything is varint-encoded; an idle frame costs a
+// handful of bytes.
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+    last_timestamp: Duration,
+    buffer: Vec<u8>,
+}
+
+impl InputRecorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<InputRecorder, RlibError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        Ok(InputRecorder {
+            writer,
+            last_timestamp: Duration::ZERO,
+            buffer: Vec::new(),
+        })
+    }
+
+    pub fn record_frame(&mut self, frame: &RecordedFrame) -> Result<(), RlibError> {
+        self.buffer.clear();
+
+        let timestamp_delta = frame.timestamp.saturating_sub(self.last_timestamp);
+        self.last_timestamp = frame.timestamp;
+
+        write_varint(&mut self.buffer, frame.index);
+        write_varint(&mut self.buffer, timestamp_delta.as_micros() as u64);
+        write_varint(&mut self.buffer, frame.frame_time.as_micros() as u64);
+        write_varint(&mut self.buffer, frame.events.len() as u64);
+
+        for event in frame.events.iter() {
+            encode_event(&mut self.buffer, event);
+        }
+
+        self.writer.write_all(&self.buffer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), RlibError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub struct InputPlayback {
+    frames: VecDeque<RecordedFrame>,
+}
+
+impl InputPlayback {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<InputPlayback, RlibError> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+
+        InputPlayback::from_bytes(&data)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<InputPlayback, RlibError> {
+        if data.len() < 5 || &data[0..4] != MAGIC {
+            return Err(RlibError::Parse("not an input recording".to_string()));
+        }
+
+        if data[4] != VERSION {
+            return Err(RlibError::Parse(
+                "unsupported input recording version".to_string(),
+            ));
+        }
+
+        let mut reader = Reader { data, pos: 5 };
+        let mut frames = VecDeque::new();
+        let mut timestamp = Duration::ZERO;
+
+        while !reader.is_empty() {
+            let index = reader.varint()?;
+            timestamp = timestamp
+                .checked_add(Duration::from_micros(reader.varint()?))
+                .ok_or_else(|| malformed("timestamp overflow in input recording"))?;
+            let frame_time = Duration::from_micros(reader.varint()?);
+            let event_count = reader.varint()?;
+
+            let mut events = Vec::new();
+            for _ in 0..event_count {
+                events.push(decode_event(&mut reader)?);
+            }
+
+            frames.push_back(RecordedFrame {
+                index,
+                timestamp,
+                frame_time,
+                events,
+            });
+        }
+
+        Ok(InputPlayback { frames })
+    }
+
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        self.frames.pop_front()
+    }
+
+    pub fn get_remaining_frames(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+fn malformed(message: &str) -> RlibError {
+    RlibError::Parse(message.to_string())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_signed(out: &mut Vec<u8>, value: i32) {
+    // Zigzag so small negative mouse deltas stay one byte.
+    write_varint(out, ((value << 1) ^ (value >> 31)) as u32 as u64);
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_varint(out, value.len() as u64);
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn modifier_bits(modifiers: &Modifiers) -> u8 {
+    (modifiers.shift as u8)
+        | (modifiers.ctrl as u8) << 1
+        | (modifiers.alt as u8) << 2
+        | (modifiers.gui as u8) << 3
+        | (modifiers.caps_lock as u8) << 4
+        | (modifiers.num_lock as u8) << 5
+}
+
+fn modifiers_from_bits(bits: u8) -> Modifiers {
+    Modifiers {
+        shift: bits & 1 != 0,
+        ctrl: bits & (1 << 1) != 0,
+        alt: bits & (1 << 2) != 0,
+        gui: bits & (1 << 3) != 0,
+        caps_lock: bits & (1 << 4) != 0,
+        num_lock: bits & (1 << 5) != 0,
+    }
+}
+
+fn encode_event(out: &mut Vec<u8>, event: &InputEvent) {
+    match event {
+        InputEvent::KeyDown {
+            key,
+            modifiers,
+            repeat,
+        } => {
+            out.push(0);
+            out.push(*key as u8);
+            out.push(modifier_bits(modifiers));
+            out.push(*repeat as u8);
+        }
+        InputEvent::KeyUp { key, modifiers } => {
+            out.push(1);
+            out.push(*key as u8);
+            out.push(modifier_bits(modifiers));
+        }
+        InputEvent::MouseButtonDown {
+            button,
+            x,
+            y,
+            clicks,
+        } => {
+            out.push(2);
+            out.push(*button as u8);
+            write_signed(out, *x);
+            write_signed(out, *y);
+            out.push(*clicks);
+        }
+        InputEvent::MouseButtonUp { button, x, y } => {
+            out.push(3);
+            out.push(*button as u8);
+            write_signed(out, *x);
+            write_signed(out, *y);
+        }
+        InputEvent::MouseMotion { x, y, dx, dy } => {
+            out.push(4);
+            write_signed(out, *x);
+            write_signed(out, *y);
+            write_signed(out, *dx);
+            write_signed(out, *dy);
+        }
+        InputEvent::MouseWheel { x, y } => {
+            out.push(5);
+            write_signed(out, *x);
+            write_signed(out, *y);
+        }
+        InputEvent::TextInput { text } => {
+            out.push(6);
+            write_string(out, text);
+        }
+        InputEvent::ControllerAdded { id, name } => {
+            out.push(7);
+            write_varint(out, id.0 as u64);
+            write_string(out, name);
+        }
+        InputEvent::ControllerRemoved { id } => {
+            out.push(8);
+            write_varint(out, id.0 as u64);
+        }
+        InputEvent::ControllerButtonDown { id, button } => {
+            out.push(9);
+            write_varint(out, id.0 as u64);
+            out.push(*button as u8);
+        }
+        InputEvent::ControllerButtonUp { id, button } => {
+            out.push(10);
+            write_varint(out, id.0 as u64);
+            out.push(*button as u8);
+        }
+        InputEvent::ControllerAxisMotion { id, axis, value } => {
+            out.push(11);
+            write_varint(out, id.0 as u64);
+            out.push(*axis as u8);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+fn decode_event(reader: &mut Reader) -> Result<InputEvent, RlibError> {
+    let event = match reader.byte()? {
+        0 => InputEvent::KeyDown {
+            key: reader.key()?,
+            modifiers: modifiers_from_bits(reader.byte()?),
+            repeat: reader.byte()? != 0,
+        },
+        1 => InputEvent::KeyUp {
+            key: reader.key()?,
+            modifiers: modifiers_from_bits(reader.byte()?),
+        },
+        2 => InputEvent::MouseButtonDown {
+            button: reader.mouse_button()?,
+            x: reader.signed()?,
+            y: reader.signed()?,
+            clicks: reader.byte()?,
+        },
+        3 => InputEvent::MouseButtonUp {
+            button: reader.mouse_button()?,
+            x: reader.signed()?,
+            y: reader.signed()?,
+        },
+        4 => InputEvent::MouseMotion {
+            x: reader.signed()?,
+            y: reader.signed()?,
+            dx: reader.signed()?,
+            dy: reader.signed()?,
+        },
+        5 => InputEvent::MouseWheel {
+            x: reader.signed()?,
+            y: reader.signed()?,
+        },
+        6 => InputEvent::TextInput {
+            text: reader.string()?,
+        },
+        7 => InputEvent::ControllerAdded {
+            id: reader.controller_id()?,
+            name: reader.string()?,
+        },
+        8 => InputEvent::ControllerRemoved {
+            id: reader.controller_id()?,
+        },
+        9 => InputEvent::ControllerButtonDown {
+            id: reader.controller_id()?,
+            button: reader.controller_button()?,
+        },
+        10 => InputEvent::ControllerButtonUp {
+            id: reader.controller_id()?,
+            button: reader.controller_button()?,
+        },
+        11 => InputEvent::ControllerAxisMotion {
+            id: reader.controller_id()?,
+            axis: reader.controller_axis()?,
+            value: f32::from_le_bytes(reader.bytes(4)?.try_into().unwrap()),
+        },
+        _ => return Err(malformed("unknown input event tag")),
+    };
+
+    Ok(event)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], RlibError> {
+        // `count` can come straight from a length in the file.
+        let end = self
+            .pos
+            .checked_add(count)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| malformed("truncated input recording"))?;
+
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, RlibError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, RlibError> {
+        let mut value: u64 = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+
+            shift += 7;
+            if shift >= 64 {
+                return Err(malformed("malformed varint in input recording"));
+            }
+        }
+    }
+
+    fn signed(&mut self) -> Result<i32, RlibError> {
+        let value = self.varint()? as u32;
+        Ok(((value >> 1) as i32) ^ -((value & 1) as i32))
+    }
+
+    fn string(&mut self) -> Result<String, RlibError> {
+        let len = usize::try_from(self.varint()?)
+            .map_err(|_| malformed("string too long in input recording"))?;
+        String::from_utf8(self.bytes(len)?.to_vec())
+            .map_err(|_| malformed("invalid utf-8 in input recording"))
+    }
+
+    fn key(&mut self) -> Result<Key, RlibError> {
+        Key::ALL
+            .get(self.byte()? as usize)
+            .copied()
+            .ok_or_else(|| malformed("unknown key in input recording"))
+    }
+
+    fn mouse_button(&mut self) -> Result<MouseButton, RlibError> {
+        MouseButton::ALL
+            .get(self.byte()? as usize)
+            .copied()
+            .ok_or_else(|| malformed("unknown mouse button in input recording"))
+    }
+
+    fn controller_id(&mut self) -> Result<ControllerId, RlibError> {
+        Ok(ControllerId(self.varint()? as u32))
+    }
+
+    fn controller_button(&mut self) -> Result<ControllerButton, RlibError> {
+        ControllerButton::ALL
+            .get(self.byte()? as usize)
+            .copied()
+            .ok_or_else(|| malformed("unknown controller button in input recording"))
+    }
+
+    fn controller_axis(&mut self) -> Result<ControllerAxis, RlibError> {
+        ControllerAxis::ALL
+            .get(self.byte()? as usize)
+            .copied()
+            .ok_or_else(|| malformed("unknown controller axis in input recording"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            300,
+            16_383,
+            16_384,
+            u32::MAX as u64,
+            u64::MAX,
+        ] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+
+            let mut reader = Reader { data: &out, pos: 0 };
+            assert_eq!(reader.varint().unwrap(), value);
+            assert!(reader.is_empty());
+        }
+
+        let mut out = Vec::new();
+        write_varint(&mut out, 127);
+        assert_eq!(out.len(), 1);
+        write_varint(&mut out, 128);
+        assert_eq!(out.len(), 3);
+    }
+
+    #[test]
+    fn zigzag_round_trips_and_keeps_small_values_short() {
+        for value in [0, 1, -1, 63, -64, 64, -65, i32::MAX, i32::MIN] {
+            let mut out = Vec::new();
+            write_signed(&mut out, value);
+
+            let mut reader = Reader { data: &out, pos: 0 };
+            assert_eq!(reader.signed().unwrap(), value);
+        }
+
+        let mut out = Vec::new();
+        write_signed(&mut out, -64);
+        assert_eq!(out, [127]);
+    }
+
+    #[test]
+    fn truncated_varint_is_an_error() {
+        let mut reader = Reader {
+            data: &[0x80, 0x80],
+            pos: 0,
+        };
+        assert!(reader.varint().is_err());
+    }
+
+    #[test]
+    fn huge_length_prefix_is_an_error() {
+        let mut out = Vec::new();
+        write_varint(&mut out, u64::MAX);
+        out.extend_from_slice(b"abc");
+
+        let mut reader = Reader { data: &out, pos: 0 };
+        assert!(matches!(reader.string(), Err(RlibError::Parse(_))));
+
+        let mut reader = Reader {
+            data: &[1, 2, 3],
+            pos: 2,
+        };
+        assert!(matches!(reader.bytes(usize::MAX), Err(RlibError::Parse(_))));
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION);
+        // Index, timestamp and frame time, then nothing.
+        write_varint(&mut data, 0);
+        write_varint(&mut data, 16_667);
+        write_varint(&mut data, 16_667);
+
+        assert!(matches!(
+            InputPlayback::from_bytes(&data),
+            Err(RlibError::Parse(_))
+        ));
+
+        // An event count with fewer events behind it.
+        write_varint(&mut data, 3);
+        assert!(matches!(
+            InputPlayback::from_bytes(&data),
+            Err(RlibError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn frames_round_trip_through_a_file() {
+        let modifiers = Modifiers {
+            shift: true,
+            caps_lock: true,
+            ..Modifiers::default()
+        };
+        let frames = [
+            RecordedFrame {
+                index: 0,
+                timestamp: Duration::from_micros(16_667),
+                frame_time: Duration::from_micros(16_667),
+                events: vec![
+                    InputEvent::KeyDown {
+                        key: Key::W,
+                        modifiers,
+                        repeat: false,
+                    },
+                    InputEvent::MouseButtonDown {
+                        button: MouseButton::Left,
+                        x: 10,
+                        y: -3,
+                        clicks: 2,
+                    },
+                    InputEvent::MouseMotion {
+                        x: 640,
+                        y: 360,
+                        dx: -5,
+                        dy: 7,
+                    },
+                    InputEvent::MouseWheel { x: 0, y: -1 },
+                    InputEvent::TextInput {
+                        text: "héllo".to_string(),
+                    },
+                ],
+            },
+            RecordedFrame {
+                index: 1,
+                timestamp: Duration::from_micros(33_334),
+                frame_time: Duration::from_micros(16_667),
+                events: Vec::new(),
+            },
+            RecordedFrame {
+                index: 2,
+                timestamp: Duration::from_micros(50_001),
+                frame_time: Duration::from_micros(16_667),
+                events: vec![
+                    InputEvent::KeyUp {
+                        key: Key::W,
+                        modifiers: Modifiers::default(),
+                    },
+                    InputEvent::MouseButtonUp {
+                        button: MouseButton::Left,
+                        x: 11,
+                        y: -2,
+                    },
+                    InputEvent::ControllerAdded {
+                        id: ControllerId(1),
+                        name: "Pad".to_string(),
+                    },
+                    InputEvent::ControllerButtonDown {
+                        id: ControllerId(1),
+                        button: ControllerButton::Start,
+                    },
+                    InputEvent::ControllerButtonUp {
+                        id: ControllerId(1),
+                        button: ControllerButton::Start,
+                    },
+                    InputEvent::ControllerAxisMotion {
+                        id: ControllerId(1),
+                        axis: ControllerAxis::TriggerRight,
+                        value: 0.25,
+                    },
+                    InputEvent::ControllerRemoved {
+                        id: ControllerId(1),
+                    },
+                ],
+            },
+        ];
+
+        let dir = TempDir::new("recording");
+        let path = dir.join("test.rlir");
+
+        let mut recorder = InputRecorder::create(&path).unwrap();
+        for frame in frames.iter() {
+            recorder.record_frame(frame).unwrap();
+        }
+
+        // Readable without dropping or flushing the recorder first.
+        let mut playback = InputPlayback::open(&path).unwrap();
+        drop(recorder);
+
+        assert_eq!(playback.get_remaining_frames(), frames.len());
+        for frame in frames.iter() {
+            assert_eq!(playback.next_frame().as_ref(), Some(frame));
+        }
+        assert_eq!(playback.next_frame(), None);
+    }
+
+    #[test]
+    fn bad_headers_are_rejected() {
+        assert!(InputPlayback::from_bytes(b"RLI").is_err());
+        assert!(InputPlayback::from_bytes(b"NOPE\x01").is_err());
+        assert!(InputPlayback::from_bytes(&[b'R', b'L', b'I', b'R', VERSION + 1]).is_err());
+        assert_eq!(
+            InputPlayback::from_bytes(&[b'R', b'L', b'I', b'R', VERSION])
+                .unwrap()
+                .get_remaining_frames(),
+            0
+        );
+    }
+}
@@ -2,117 +2,466 @@
 extern crate log;
 extern crate simplelog;
 use simplelog::*;
+use std::fs::File;
 
 extern crate sdl2;
+use sdl2::video::{GLContext, SwapInterval};
 
 extern crate gl;
 
+// Lets `#[derive(Vertex)]` and `#[derive(Uniforms)]` expand to `::rlib::...`
+// paths inside this crate too.
+extern crate self as rlib;
+
+pub mod config;
+pub mod context;
+pub mod error;
 pub mod gfx;
+pub mod headless;
+pub mod input;
+pub mod time;
+pub mod window;
+
+#[cfg(test)]
+mod test_util;
+
+pub use config::RlibConfig;
+use config::{VSync, WindowMode};
+pub use context::Context;
+pub use error::RlibError;
+use gfx::graphics::Graphics;
+use input::recording::{InputPlayback, InputRecorder, RecordedFrame};
+use input::{ActionMap, Controllers, Input, InputEvent};
+
+use time::Time;
+use window::{Window, WindowEvent};
 
 pub trait RLibApp {
-    fn new() -> Self;
-    fn init(&mut self);
-    fn render(&mut self);
-    fn on_key(&mut self, scancode: u32);
-}
+    fn new(ctx: &mut Context) -> Self;
+    fn init(&mut self, ctx: &mut Context);
+    fn update(&mut self, _ctx: &mut Context, _dt: f32) {}
 
-pub struct RlibConfig {
-    pub window_title: String,
-    pub window_width: u32,
-    pub window_height: u32,
-}
+    // `ctx.get_time().get_alpha()` is how far we are between the last update
+    // and the next one, for apps that want to interpolate their rendered state.
+    fn render(&mut self, ctx: &mut Context);
 
-impl Default for RlibConfig {
-    fn default() -> RlibConfig {
-        RlibConfig {
-            window_title: "Title".to_string(),
-            window_width: 800,
-            window_height: 600,
-        }
-    }
+    fn on_input(&mut self, _ctx: &mut Context, _event: &InputEvent) {}
+    fn on_window_event(&mut self, _ctx: &mut Context, _event: &WindowEvent) {}
 }
 
-pub fn init<App: RLibApp>(config: RlibConfig) {
-    TermLogger::init(
-        LevelFilter::max(),
-        Config::default(),
-        TerminalMode::Mixed,
-        ColorChoice::Auto,
-    )
-    .unwrap();
+// How long to sleep per frame while rendering is paused, so a minimised
+// window doesn't spin a core.
+const PAUSED_FRAME_SLEEP: std::time::Duration = std::time::Duration::from_millis(16);
+
+pub fn init<App: RLibApp>(config: RlibConfig) -> Result<i32, RlibError> {
+    init_logger(&config);
+    config.validate()?;
 
     info!("Initializing rlib");
     info!("Initializing SDL");
-    let sdl = sdl2::init().unwrap();
-    let sdl_video = sdl.video().unwrap();
+    if config.headless.is_some() {
+        info!("Running headless");
+        headless::select_offscreen_driver();
+    }
+
+    let sdl = sdl2::init().map_err(RlibError::Sdl)?;
+    let sdl_video = sdl.video().map_err(|e| {
+        if config.headless.is_some() {
+            headless::unavailable(&e)
+        } else {
+            RlibError::Sdl(e)
+        }
+    })?;
+
+    if config.headless.is_some() {
+        headless::check_video_driver(&sdl_video)?;
+    }
 
     let gl_attr = sdl_video.gl_attr();
     gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
-    gl_attr.set_context_version(4, 5);
-
-    let window = sdl_video
-        .window(
-            &config.window_title,
-            config.window_width,
-            config.window_height,
-        )
-        .opengl()
-        .resizable()
+    gl_attr.set_depth_size(config.depth_bits);
+    gl_attr.set_stencil_size(config.stencil_bits);
+
+    if config.msaa_samples > 0 {
+        gl_attr.set_multisample_buffers(1);
+        gl_attr.set_multisample_samples(config.msaa_samples);
+    }
+
+    let mut window_builder = sdl_video.window(
+        &config.window_title,
+        config.window_width,
+        config.window_height,
+    );
+    window_builder.opengl();
+
+    if config.resizable {
+        window_builder.resizable();
+    }
+
+    if config.high_dpi {
+        window_builder.allow_highdpi();
+    }
+
+    match config.window_mode {
+        WindowMode::Windowed => {}
+        WindowMode::Fullscreen => {
+            window_builder.fullscreen();
+        }
+        WindowMode::Borderless => {
+            window_builder.fullscreen_desktop();
+        }
+    }
+
+    if config.headless.is_some() {
+        window_builder.hidden();
+    }
+
+    let mut sdl_window = window_builder
         .build()
-        .unwrap();
+        .map_err(|e| RlibError::Sdl(e.to_string()))?;
+
+    if let Some((min_width, min_height)) = config.min_window_size {
+        if let Err(e) = sdl_window.set_minimum_size(min_width, min_height) {
+            warn!("Failed to set minimum window size: {}", e);
+        }
+    }
+
+    let gl_context = create_gl_context(&sdl_window, &config)?;
+    gl::load_with(|s| sdl_video.gl_get_proc_address(s) as *const std::os::raw::c_void);
+    check_gl_version()?;
+
+    let swap_interval = match config.vsync {
+        VSync::Off => SwapInterval::Immediate,
+        VSync::On => SwapInterval::VSync,
+        VSync::Adaptive => SwapInterval::LateSwapTearing,
+    };
+
+    if let Err(e) = sdl_video.gl_set_swap_interval(swap_interval) {
+        warn!("Failed to set swap interval {:?}: {}", config.vsync, e);
+
+        if config.vsync == VSync::Adaptive {
+            if let Err(e) = sdl_video.gl_set_swap_interval(SwapInterval::VSync) {
+                warn!("Failed to fall back to vsync: {}", e);
+            }
+        }
+    }
+
+    let mut timestep = time::FixedTimestep::new(config.tick_rate, config.max_frame_time);
+
+    let mut controllers = match sdl.game_controller() {
+        Ok(subsystem) => Controllers::new(Some(subsystem)),
+        Err(e) => {
+            warn!("Game controllers unavailable: {}", e);
+            Controllers::new(None)
+        }
+    };
+
+    if let Some(path) = &config.controller_mappings {
+        match controllers.load_mappings(path) {
+            Ok(count) => info!("Loaded {} controller mappings from {}", count, path),
+            Err(e) => error!("Failed to load controller mappings from {}: {}", path, e),
+        }
+    }
+
+    let mut input = Input::new();
+    input.set_dead_zone(config.controller_dead_zone);
 
-    let _gl_context = window.gl_create_context().unwrap();
-    let _gl = gl::load_with(|s| sdl_video.gl_get_proc_address(s) as *const std::os::raw::c_void);
+    if let Some(path) = &config.action_bindings {
+        match ActionMap::load(path) {
+            Ok(action_map) => input.set_action_map(action_map),
+            Err(e) => error!("Failed to load action bindings from {}: {}", path, e),
+        }
+    }
+
+    let (drawable_width, drawable_height) = sdl_window.drawable_size();
+    let mut ctx = Context {
+        gfx: Graphics::new(gl_context, &config, drawable_width, drawable_height)?,
+        window: Window::new(sdl_window),
+        input,
+        time: Time::new(timestep.get_dt()),
+        quit_requested: false,
+    };
+
+    let mut app = App::new(&mut ctx);
+    app.init(&mut ctx);
 
-    let mut app = App::new();
-    app.init();
+    let mut recorder =
+        config
+            .record_input
+            .as_ref()
+            .and_then(|path| match InputRecorder::create(path) {
+                Ok(recorder) => {
+                    info!("Recording input to {}", path);
+                    Some(recorder)
+                }
+                Err(e) => {
+                    error!("Failed to create input recording {}: {}", path, e);
+                    None
+                }
+            });
+
+    let mut playback =
+        config
+            .replay_input
+            .as_ref()
+            .and_then(|path| match InputPlayback::open(path) {
+                Ok(playback) => {
+                    info!("Replaying input from {}", path);
+                    Some(playback)
+                }
+                Err(e) => {
+                    error!("Failed to open input recording {}: {}", path, e);
+                    None
+                }
+            });
 
-    let mut window_width: i32 = config.window_width as i32;
-    let mut window_height: i32 = config.window_height as i32;
+    let start_time = std::time::Instant::now();
+    let mut frame_index: u64 = 0;
+    let mut frame_events: Vec<InputEvent> = Vec::new();
+    let mut window_events: Vec<WindowEvent> = Vec::new();
+    let mut gl_error_count: u32 = 0;
+    let mut replay_desynced = false;
 
-    let mut event_pump = sdl.event_pump().unwrap();
+    let mut event_pump = sdl.event_pump().map_err(RlibError::Sdl)?;
     'main_loop: loop {
+        let frame_start = std::time::Instant::now();
+        frame_events.clear();
+        window_events.clear();
+
         for event in event_pump.poll_iter() {
+            if let Some(window_event) = ctx.window.translate(&event) {
+                window_events.push(window_event);
+                continue;
+            }
+
             match event {
                 sdl2::event::Event::Quit { .. } => break 'main_loop,
-                sdl2::event::Event::Window {
-                    timestamp: _,
-                    window_id: _,
-                    win_event,
-                } => match win_event {
-                    sdl2::event::WindowEvent::Resized(w, h) => {
-                        window_height = h;
-                        window_width = w;
+                // While replaying, live input is dropped so the recording is
+                // the only thing driving the app.
+                _ if playback.is_some() => {}
+                _ => {
+                    let input_event = controllers
+                        .translate(&event)
+                        .or_else(|| InputEvent::from_sdl(&event));
+
+                    if let Some(input_event) = input_event {
+                        frame_events.push(input_event);
+                    }
+                }
+            }
+        }
+
+        let frame_time = match playback.as_mut() {
+            Some(playback) => match playback.next_frame() {
+                Some(frame) if frame.index != frame_index => {
+                    error!(
+                        "Input replay out of sync: expected frame {}, recording has frame {}",
+                        frame_index, frame.index
+                    );
+                    replay_desynced = true;
+                    break 'main_loop;
+                }
+                Some(frame) => {
+                    frame_events = frame.events;
+                    timestep.advance_by(frame.frame_time)
+                }
+                None => {
+                    info!("Input replay finished after {} frames", frame_index);
+                    break 'main_loop;
+                }
+            },
+            // Headless runs advance exactly one tick per frame so their
+            // output doesn't depend on how fast the CI machine is.
+            None if config.headless.is_some() => {
+                let step = timestep.get_step();
+                timestep.advance_by(step)
+            }
+            None => timestep.advance(),
+        };
+
+        ctx.time.begin_frame(frame_index, frame_time);
+        ctx.gfx.poll_hot_reload();
+
+        for window_event in window_events.iter() {
+            if let WindowEvent::Resized {
+                drawable_width,
+                drawable_height,
+                ..
+            } = window_event
+            {
+                ctx.gfx
+                    .set_viewport(0, 0, *drawable_width as i32, *drawable_height as i32);
+            }
+
+            app.on_window_event(&mut ctx, window_event);
+        }
+
+        for input_event in frame_events.iter() {
+            ctx.input.handle_event(input_event);
+            app.on_input(&mut ctx, input_event);
+        }
+
+        if let Some(active_recorder) = recorder.as_mut() {
+            let frame = RecordedFrame {
+                index: frame_index,
+                timestamp: start_time.elapsed(),
+                frame_time,
+                events: std::mem::take(&mut frame_events),
+            };
+
+            if let Err(e) = active_recorder.record_frame(&frame) {
+                error!("Failed to write input recording, stopping: {}", e);
+                recorder = None;
+            }
+        }
+
+        while timestep.tick() {
+            app.update(&mut ctx, timestep.get_dt());
+            ctx.time.tick_index += 1;
+            ctx.input.clear_edges();
+        }
+
+        ctx.time.alpha = timestep.get_alpha();
+
+        if config.pause_when_minimized && ctx.window.is_minimized() {
+            std::thread::sleep(PAUSED_FRAME_SLEEP);
+        } else {
+            ctx.gfx.begin_frame(ctx.time.get_elapsed() as f32);
+            app.render(&mut ctx);
+
+            if let Some(headless) = &config.headless {
+                gl_error_count += headless::drain_gl_errors();
+
+                if frame_index + 1 >= headless.frames {
+                    if let Some(path) = &headless.capture_path {
+                        let (width, height) = ctx.window.get_drawable_size();
+                        match headless::capture_framebuffer(width, height, path) {
+                            Ok(()) => info!("Captured final frame to {}", path),
+                            Err(e) => {
+                                error!("Failed to capture final frame to {}: {}", path, e);
+                                gl_error_count += 1;
+                            }
+                        }
                     }
-                    _ => {}
-                },
-                sdl2::event::Event::KeyDown {
-                    timestamp,
-                    window_id,
-                    keycode,
-                    scancode,
-                    keymod,
-                    repeat,
-                } => {
-                    app.on_key(scancode.unwrap() as u32);
                 }
-                _ => {}
+            } else if let Some(max_fps) = config.max_fps {
+                let min_frame_time =
+                    std::time::Duration::from_secs_f64(1.0 / max_fps.max(1) as f64);
+                let elapsed = frame_start.elapsed();
+
+                if elapsed < min_frame_time {
+                    std::thread::sleep(min_frame_time - elapsed);
+                }
+            }
+
+            ctx.window.swap();
+        }
+
+        gfx::deletion_queue::flush();
+
+        frame_index += 1;
+
+        if ctx.quit_requested {
+            break 'main_loop;
+        }
+
+        if let Some(headless) = &config.headless {
+            if frame_index >= headless.frames {
+                break 'main_loop;
             }
         }
+    }
+
+    if let Some(recorder) = recorder.as_mut() {
+        if let Err(e) = recorder.flush() {
+            error!("Failed to flush input recording: {}", e);
+        }
+    }
+
+    if config.headless.is_some() {
+        info!(
+            "Headless run finished after {} frames with {} errors",
+            frame_index, gl_error_count
+        );
+    }
+
+    if gl_error_count > 0 || replay_desynced {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
+
+fn init_logger(config: &RlibConfig) {
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![TermLogger::new(
+        config.log_level,
+        Config::default(),
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    )];
+
+    let mut log_file_error = None;
+    if let Some(path) = &config.log_file {
+        match File::create(path) {
+            Ok(file) => loggers.push(WriteLogger::new(config.log_level, Config::default(), file)),
+            Err(e) => log_file_error = Some(e),
+        }
+    }
+
+    if CombinedLogger::init(loggers).is_err() {
+        warn!("A logger is already installed, keeping it");
+    }
+
+    if let Some(e) = log_file_error {
+        error!("Failed to open log file: {}", e);
+    }
+}
+
+fn create_gl_context(
+    window: &sdl2::video::Window,
+    config: &RlibConfig,
+) -> Result<GLContext, RlibError> {
+    let gl_attr = window.subsystem().gl_attr();
+    let mut last_error = String::new();
+
+    if config.gl_debug {
+        gl_attr.set_context_flags().debug().set();
+    }
+
+    for &(major, minor) in std::iter::once(&config.gl_version).chain(&config.gl_fallback_versions) {
+        gl_attr.set_context_version(major, minor);
 
-        unsafe {
-            gl::Viewport(
-                0,
-                0,
-                i32::try_from(window_width).unwrap(),
-                i32::try_from(window_height).unwrap(),
-            );
+        match window.gl_create_context() {
+            Ok(context) => {
+                info!("Created OpenGL {}.{} core context", major, minor);
+                return Ok(context);
+            }
+            Err(e) => {
+                warn!("Failed to create OpenGL {}.{} context: {}", major, minor, e);
+                last_error = e;
+            }
         }
+    }
 
-        app.render();
+    Err(RlibError::Sdl(last_error))
+}
 
-        std::thread::sleep(std::time::Duration::from_millis(10));
+// Drivers may hand out a different version than the one asked for.
+fn check_gl_version() -> Result<(), RlibError> {
+    let mut major: i32 = 0;
+    let mut minor: i32 = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
 
-        window.gl_swap_window();
+    let required = config::MIN_GL_VERSION;
+    if (major, minor) < (required.0 as i32, required.1 as i32) {
+        return Err(RlibError::GlVersion {
+            required,
+            actual: (major, minor),
+        });
     }
+
+    Ok(())
 }
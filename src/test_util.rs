@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+
+// A fresh directory under the system temp dir, removed when dropped so a
+// failing assertion doesn't leave files behind. `name` has to be unique
+// across the crate's tests, which run in parallel.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("rlib-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub(crate) fn get_path(&self) -> &Path {
+        &self.0
+    }
+
+    pub(crate) fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    pub(crate) fn write<C: AsRef<[u8]>>(&self, name: &str, contents: C) {
+        std::fs::write(self.join(name), contents).unwrap();
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::{Duration, Instant};
+
+pub struct FixedTimestep {
+    step: Duration,
+    max_frame_time: Duration,
+    accumulator: Duration,
+    last_time: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(tick_rate: u32, max_frame_time: f32) -> FixedTimestep {
+        FixedTimestep {
+            step: Duration::from_secs_f64(1.0 / tick_rate.max(1) as f64),
+            max_frame_time: Duration::from_secs_f32(max_frame_time),
+            accumulator: Duration::ZERO,
+            last_time: Instant::now(),
+        }
+    }
+
+    pub fn advance(&mut self) -> Duration {
+        let now = Instant::now();
+        let frame_time = now - self.last_time;
+        self.last_time = now;
+
+        self.advance_by(frame_time)
+    }
+
+    // Clamping keeps a long stall (debugger, window drag) from queueing up
+    // more updates than we can ever catch up on.
+    pub fn advance_by(&mut self, frame_time: Duration) -> Duration {
+        let frame_time = frame_time.min(self.max_frame_time);
+        self.accumulator += frame_time;
+
+        frame_time
+    }
+
+    pub fn tick(&mut self) -> bool {
+        if self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn get_step(&self) -> Duration {
+        self.step
+    }
+
+    pub fn get_dt(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    pub fn get_alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Time {
+    pub(crate) dt: f32,
+    pub(crate) alpha: f32,
+    pub(crate) frame_time: f32,
+    pub(crate) elapsed: f64,
+    pub(crate) frame_index: u64,
+    pub(crate) tick_index: u64,
+    pub(crate) fps: f32,
+}
+
+impl Time {
+    pub(crate) fn new(dt: f32) -> Time {
+        Time {
+            dt,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn begin_frame(&mut self, frame_index: u64, frame_time: Duration) {
+        self.frame_index = frame_index;
+        self.frame_time = frame_time.as_secs_f32();
+        self.elapsed += frame_time.as_secs_f64();
+
+        if self.frame_time > 0.0 {
+            // Smooth the counter so it is readable when drawn every frame.
+            let instant_fps = 1.0 / self.frame_time;
+            self.fps = if self.fps == 0.0 {
+                instant_fps
+            } else {
+                self.fps * 0.9 + instant_fps * 0.1
+            };
+        }
+    }
+
+    pub fn get_dt(&self) -> f32 {
+        self.dt
+    }
+
+    pub fn get_alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    pub fn get_frame_time(&self) -> f32 {
+        self.frame_time
+    }
+
+    pub fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn get_frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    pub fn get_tick_index(&self) -> u64 {
+        self.tick_index
+    }
+
+    pub fn get_fps(&self) -> f32 {
+        self.fps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advancing_by_the_step_ticks_exactly_once() {
+        for tick_rate in [25, 30, 60, 144] {
+            let mut timestep = FixedTimestep::new(tick_rate, 0.25);
+
+            for frame in 0..1000 {
+                let step = timestep.get_step();
+                timestep.advance_by(step);
+
+                assert!(
+                    timestep.tick(),
+                    "no tick at {} Hz, frame {}",
+                    tick_rate,
+                    frame
+                );
+                assert!(
+                    !timestep.tick(),
+                    "two ticks at {} Hz, frame {}",
+                    tick_rate,
+                    frame
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::config::WindowMode;
+use crate::error::RlibError;
+use sdl2::event::Event;
+use sdl2::video::FullscreenType;
+use std::path::PathBuf;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum WindowEvent {
+    // `drawable_width`/`drawable_height` are in pixels and differ from the
+    // window size on high-DPI displays; they are what the viewport uses.
+    Resized {
+        width: u32,
+        height: u32,
+        drawable_width: u32,
+        drawable_height: u32,
+    },
+    FocusGained,
+    FocusLost,
+    Minimized,
+    Maximized,
+    Restored,
+    DisplayChanged {
+        display_index: i32,
+        dpi: Option<f32>,
+    },
+    FileDropped {
+        path: PathBuf,
+    },
+    TextDropped {
+        text: String,
+    },
+}
+
+pub struct Window {
+    window: sdl2::video::Window,
+    width: u32,
+    height: u32,
+    display_index: i32,
+    focused: bool,
+    minimized: bool,
+}
+
+impl Window {
+    pub(crate) fn new(window: sdl2::video::Window) -> Window {
+        let (width, height) = window.size();
+        let display_index = window.display_index().unwrap_or(0);
+
+        Window {
+            window,
+            width,
+            height,
+            display_index,
+            focused: true,
+            minimized: false,
+        }
+    }
+
+    pub(crate) fn translate(&mut self, event: &Event) -> Option<WindowEvent> {
+        use sdl2::event::WindowEvent as SdlWindowEvent;
+
+        match event {
+            Event::Window { win_event, .. } => match win_event {
+                // `SizeChanged` also fires for programmatic resizes, unlike
+                // `Resized`, so it is the only one we listen to.
+                SdlWindowEvent::SizeChanged(width, height) => {
+                    self.width = (*width).max(0) as u32;
+                    self.height = (*height).max(0) as u32;
+                    let (drawable_width, drawable_height) = self.window.drawable_size();
+
+                    Some(WindowEvent::Resized {
+                        width: self.width,
+                        height: self.height,
+                        drawable_width,
+                        drawable_height,
+                    })
+                }
+                SdlWindowEvent::FocusGained => {
+                    self.focused = true;
+                    Some(WindowEvent::FocusGained)
+                }
+                SdlWindowEvent::FocusLost => {
+                    self.focused = false;
+                    Some(WindowEvent::FocusLost)
+                }
+                SdlWindowEvent::Minimized => {
+                    self.minimized = true;
+                    Some(WindowEvent::Minimized)
+                }
+                SdlWindowEvent::Maximized => {
+                    self.minimized = false;
+                    Some(WindowEvent::Maximized)
+                }
+                SdlWindowEvent::Restored => {
+                    self.minimized = false;
+                    Some(WindowEvent::Restored)
+                }
+                // SDL 2.0.16 has no display event of its own, so moving onto
+                // another monitor is picked up from the display index.
+                SdlWindowEvent::Moved(..) => {
+                    let display_index = self.window.display_index().ok()?;
+
+                    if display_index == self.display_index {
+                        return None;
+                    }
+
+                    self.display_index = display_index;
+                    Some(WindowEvent::DisplayChanged {
+                        display_index,
+                        dpi: self.get_dpi(),
+                    })
+                }
+                _ => None,
+            },
+            Event::DropFile { filename, .. } => Some(WindowEvent::FileDropped {
+                path: PathBuf::from(filename),
+            }),
+            Event::DropText { filename, .. } => Some(WindowEvent::TextDropped {
+                text: filename.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn get_title(&self) -> &str {
+        self.window.title()
+    }
+
+    pub fn set_title(&mut self, title: &str) -> Result<(), RlibError> {
+        self.window
+            .set_title(title)
+            .map_err(|e| RlibError::Sdl(e.to_string()))
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn set_size(&mut self, width: u32, height: u32) -> Result<(), RlibError> {
+        self.window
+            .set_size(width, height)
+            .map_err(|e| RlibError::Sdl(e.to_string()))?;
+        self.width = width;
+        self.height = height;
+
+        Ok(())
+    }
+
+    pub fn get_drawable_size(&self) -> (u32, u32) {
+        self.window.drawable_size()
+    }
+
+    pub fn get_display_index(&self) -> i32 {
+        self.display_index
+    }
+
+    pub fn get_dpi(&self) -> Option<f32> {
+        self.window
+            .subsystem()
+            .display_dpi(self.display_index)
+            .ok()
+            .map(|(diagonal, _, _)| diagonal)
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    pub fn get_window_mode(&self) -> WindowMode {
+        match self.window.fullscreen_state() {
+            FullscreenType::Off => WindowMode::Windowed,
+            FullscreenType::True => WindowMode::Fullscreen,
+            FullscreenType::Desktop => WindowMode::Borderless,
+        }
+    }
+
+    pub fn set_window_mode(&mut self, mode: WindowMode) -> Result<(), RlibError> {
+        let fullscreen_type = match mode {
+            WindowMode::Windowed => FullscreenType::Off,
+            WindowMode::Fullscreen => FullscreenType::True,
+            WindowMode::Borderless => FullscreenType::Desktop,
+        };
+
+        self.window
+            .set_fullscreen(fullscreen_type)
+            .map_err(RlibError::Sdl)
+    }
+
+    pub fn toggle_fullscreen(&mut self) -> Result<(), RlibError> {
+        match self.get_window_mode() {
+            WindowMode::Windowed => self.set_window_mode(WindowMode::Borderless),
+            _ => self.set_window_mode(WindowMode::Windowed),
+        }
+    }
+
+    pub fn get_sdl_window(&self) -> &sdl2::video::Window {
+        &self.window
+    }
+
+    pub(crate) fn swap(&self) {
+        self.window.gl_swap_window();
+    }
+}
@@ -0,0 +1,30 @@
+use rlib::headless::HeadlessConfig;
+use rlib::{Context, RLibApp, RlibConfig};
+
+struct Blank;
+
+impl RLibApp for Blank {
+    fn new(_ctx: &mut Context) -> Self {
+        Blank
+    }
+
+    fn init(&mut self, _ctx: &mut Context) {}
+
+    fn render(&mut self, _ctx: &mut Context) {}
+}
+
+#[test]
+fn runs_without_a_display() {
+    std::env::remove_var("DISPLAY");
+    std::env::remove_var("WAYLAND_DISPLAY");
+
+    let config = RlibConfig {
+        headless: Some(HeadlessConfig {
+            frames: 3,
+            capture_path: None,
+        }),
+        ..RlibConfig::default()
+    };
+
+    assert_eq!(rlib::init::<Blank>(config).unwrap(), 0);
+}